@@ -1,6 +1,8 @@
 use termion::color::Rgb;
 use std::fs;
 
+use crate::config::config_path;
+
 pub struct Colors {
     pub name: Rgb,
     pub status: Rgb,
@@ -33,8 +35,7 @@ impl Colors {
     }
     pub fn new_from_config() -> Result<Self, std::io::Error> {
         let mut selfish = Self::new_default();
-        let config_path = format!("{}{}", std::env::var("HOME").unwrap(), "/.config/bwmp/config");
-        let config_str = fs::read_to_string(config_path)?.replace(" ", "");
+        let config_str = fs::read_to_string(config_path())?.replace(" ", "");
         for line in config_str.split("\n") {
             let mut iter = line.split(":");
             match iter.next() {
@@ -58,10 +59,8 @@ impl Colors {
 
 fn parse_rgb(rgb_str: &str) -> Result<Rgb, std::io::Error> {
     let mut rgb :[u8; 3] = [0,0,0];
-    let mut t = 0;
-    for i in rgb_str.split(",") {
+    for (t, i) in rgb_str.split(",").enumerate() {
         rgb[t] = i.parse::<u8>().unwrap_or_default();
-        t+=1;
     }
     eprint!("{:?}", rgb);
     Ok(Rgb(rgb[0], rgb[1], rgb[2]))
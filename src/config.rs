@@ -0,0 +1,148 @@
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use crate::{
+    eq::{default_presets, parse_preset, Preset},
+    output::{match_backend, BackendKind},
+    pcm::PcmFormat,
+    player::{Repeat, Shuffle},
+    replaygain::{match_gain_mode, GainMode},
+};
+
+//everything that can be set in the config file and overriden with arguments
+pub struct Config {
+    pub dirs: Vec<PathBuf>,
+    //an m3u to play instead of the dirs
+    pub playlist: Option<PathBuf>,
+    pub depth: usize,
+    pub repeat: Repeat,
+    pub shuffle: Shuffle,
+    pub volume: f32,
+    //0 turns crossfading off, skip_fade is used when changing tracks by hand
+    pub crossfade: Duration,
+    pub skip_fade: Duration,
+    pub gain_mode: GainMode,
+    //dB added on top of the replaygain value
+    pub preamp: f32,
+    pub prevent_clipping: bool,
+    //the built in presets plus every eq_preset line, in that order
+    pub eq_presets: Vec<Preset>,
+    //preset the equalizer starts with, None leaves it off
+    pub eq: Option<String>,
+    pub speed: f32,
+    pub backend: BackendKind,
+    //cpal device name, None is the default output
+    pub device: Option<String>,
+    //where the file backend writes to
+    pub wav: PathBuf,
+    //set by --pcm-out, "-" is stdout
+    pub pcm_out: Option<PathBuf>,
+    pub pcm_format: PcmFormat,
+    pub pcm_rate: u32,
+    //ignore the library database and probe every file again
+    pub rescan: bool,
+    //carry on where the last session with the same dirs or playlist left off
+    pub resume: bool,
+    //set when the argument was given, a resumed session only restores the ones that werent
+    pub volume_given: bool,
+    pub repeat_given: bool,
+    pub shuffle_given: bool,
+}
+
+impl Config {
+    pub fn new_default() -> Self {
+        Self {
+            dirs: Vec::new(),
+            playlist: None,
+            depth: 16,
+            repeat: Repeat::None,
+            shuffle: Shuffle::Off,
+            volume: 1.0,
+            crossfade: Duration::ZERO,
+            skip_fade: Duration::ZERO,
+            gain_mode: GainMode::Off,
+            preamp: 0.0,
+            prevent_clipping: true,
+            eq_presets: default_presets(),
+            eq: None,
+            speed: 1.0,
+            backend: BackendKind::Device,
+            device: None,
+            wav: PathBuf::from("bwmp.wav"),
+            pcm_out: None,
+            pcm_format: PcmFormat::S16Le,
+            pcm_rate: 44100,
+            rescan: false,
+            resume: true,
+            volume_given: false,
+            repeat_given: false,
+            shuffle_given: false,
+        }
+    }
+    //reads the same file as the colors, unknown keys are skipped so both can live there
+    pub fn new_from_config() -> Result<Self, io::Error> {
+        let mut selfish = Self::new_default();
+        let config_str = fs::read_to_string(config_path())?;
+        for line in config_str.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "dir" => selfish.dirs.push(expand_home(value)),
+                "depth" => selfish.depth = value.parse().unwrap_or(selfish.depth),
+                "crossfade" => selfish.crossfade = parse_secs(value).unwrap_or(selfish.crossfade),
+                "skip_fade" => selfish.skip_fade = parse_secs(value).unwrap_or(selfish.skip_fade),
+                "replaygain" => selfish.gain_mode = match_gain_mode(value).unwrap_or_default(),
+                "preamp" => selfish.preamp = value.parse().unwrap_or(selfish.preamp),
+                "clip_prevention" => selfish.prevent_clipping = value != "false",
+                "resume" => selfish.resume = value != "false",
+                "backend" => selfish.backend = match_backend(value).unwrap_or_default(),
+                "output" => selfish.device = Some(value.to_string()),
+                "wav" => selfish.wav = expand_home(value),
+                "speed" => selfish.speed = value.parse().unwrap_or(selfish.speed),
+                "eq" => selfish.eq = (value != "off").then(|| value.to_string()),
+                "eq_preset" => {
+                    let Some(preset) = parse_preset(value) else { continue };
+                    //a preset with a taken name replaces the old one
+                    match selfish.eq_presets.iter_mut().find(|p| p.name == preset.name) {
+                        Some(old) => *old = preset,
+                        None => selfish.eq_presets.push(preset),
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(selfish)
+    }
+}
+
+pub fn config_path() -> String {
+    format!("{}{}", std::env::var("HOME").unwrap_or_default(), "/.config/bwmp/config")
+}
+
+//$XDG_CACHE_HOME/bwmp, or ~/.cache/bwmp when that isnt set
+pub fn cache_dir() -> PathBuf {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("bwmp"),
+        _ => expand_home("~/.cache/bwmp"),
+    }
+}
+
+//$XDG_DATA_HOME/bwmp, or ~/.local/share/bwmp when that isnt set
+pub fn data_dir() -> PathBuf {
+    match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join("bwmp"),
+        _ => expand_home("~/.local/share/bwmp"),
+    }
+}
+
+//"2.5" -> 2.5 seconds, negative and garbage values are None
+pub fn parse_secs(s: &str) -> Option<Duration> {
+    Duration::try_from_secs_f32(s.trim().parse().ok()?).ok()
+}
+
+//turns ~/music into /home/someone/music
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest),
+        None => PathBuf::from(path),
+    }
+}
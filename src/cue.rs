@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::track::{CueRange, Track, TrackId};
+
+//the part of a cue sheet that belongs to one FILE line
+pub struct CueSheet {
+    pub file: PathBuf,
+    title: Option<String>,
+    performer: Option<String>,
+    genre: Option<String>,
+    year: Option<u32>,
+    tracks: Vec<CueTrack>,
+}
+
+struct CueTrack {
+    number: u32,
+    title: Option<String>,
+    performer: Option<String>,
+    //INDEX 01, a track without one is never played
+    start: Option<Duration>,
+}
+
+pub fn is_cue_sheet(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+//reads a cue sheet, one CueSheet for every file it points at, relative to where the sheet is
+pub fn parse(path: &Path) -> Vec<CueSheet> {
+    let Ok(bytes) = fs::read(path) else { return Vec::new() };
+    //plenty of rippers write them in whatever the local codepage was
+    let text = String::from_utf8_lossy(&bytes);
+    let base = path.parent().unwrap_or(Path::new("."));
+    let mut sheets: Vec<CueSheet> = Vec::new();
+    //whatever comes before the first FILE is about the whole album
+    let mut album = CueSheet {
+        file: PathBuf::new(),
+        title: None,
+        performer: None,
+        genre: None,
+        year: None,
+        tracks: Vec::new(),
+    };
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let sheet = sheets.last_mut().unwrap_or(&mut album);
+        match command.to_uppercase().as_str() {
+            "FILE" => {
+                //FILE "name with spaces.flac" WAVE, the type at the end isnt needed
+                let name = match rest.strip_prefix('"') {
+                    Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+                    None => rest.rsplit_once(' ').map_or(rest, |(name, _)| name),
+                };
+                sheets.push(CueSheet {
+                    file: base.join(name),
+                    title: album.title.clone(),
+                    performer: album.performer.clone(),
+                    genre: album.genre.clone(),
+                    year: album.year,
+                    tracks: Vec::new(),
+                });
+            }
+            "TRACK" => {
+                let number = rest.split_whitespace().next().and_then(|n| n.parse().ok());
+                if let Some(number) = number {
+                    sheet.tracks.push(CueTrack { number, title: None, performer: None, start: None });
+                }
+            }
+            "TITLE" | "PERFORMER" => {
+                let value = Some(unquote(rest)).filter(|v| !v.is_empty());
+                let title = command.eq_ignore_ascii_case("TITLE");
+                match (sheet.tracks.last_mut(), title) {
+                    (Some(track), true) => track.title = value,
+                    (Some(track), false) => track.performer = value,
+                    (None, true) => sheet.title = value,
+                    (None, false) => sheet.performer = value,
+                }
+            }
+            "INDEX" => {
+                let Some(track) = sheet.tracks.last_mut() else { continue };
+                let mut parts = rest.split_whitespace();
+                if parts.next().and_then(|n| n.parse::<u32>().ok()) == Some(1) {
+                    track.start = parts.next().and_then(parse_time);
+                }
+            }
+            //REM GENRE Rock, REM DATE 1999
+            "REM" => {
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let value = unquote(value.trim());
+                match key.to_uppercase().as_str() {
+                    "GENRE" if !value.is_empty() => sheet.genre = Some(value),
+                    "DATE" => sheet.year = value.get(..4).and_then(|y| y.parse().ok()),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    sheets
+}
+
+impl CueSheet {
+    //a track for every indexed song of the sheet, whole is the file they are all in
+    pub fn split(&self, whole: &Track) -> Vec<Track> {
+        let mut starts: Vec<(&CueTrack, Duration)> =
+            self.tracks.iter().filter_map(|t| Some((t, t.start?))).collect();
+        starts.sort_by_key(|(_, start)| *start);
+        let mut tracks = Vec::new();
+        for (i, (cue_track, start)) in starts.iter().enumerate() {
+            //each song lasts until the next one starts, the last one until the file ends
+            let end = starts.get(i + 1).map(|(_, next)| *next);
+            let mut track = whole.clone();
+            track.id = TrackId::of_cue(&whole.path, cue_track.number);
+            track.title = cue_track.title.clone().unwrap_or_else(|| format!("Track {}", cue_track.number));
+            track.artist = cue_track.performer.clone().or(self.performer.clone()).or(whole.artist.clone());
+            track.album = self.title.clone().or(whole.album.clone());
+            track.genre = self.genre.clone().or(whole.genre.clone());
+            track.year = self.year.or(whole.year);
+            track.track_number = Some(cue_track.number);
+            track.duration = match end {
+                Some(end) => Some(end - *start),
+                None => whole.duration.map(|d| d.saturating_sub(*start)),
+            };
+            //the file tags are about the whole file, its track gain would be wrong for a part
+            track.replay_gain.track_gain = None;
+            track.replay_gain.track_peak = None;
+            track.cue = Some(CueRange { number: cue_track.number, start: *start, end });
+            tracks.push(track);
+        }
+        tracks
+    }
+}
+
+fn unquote(s: &str) -> String {
+    s.trim().trim_matches('"').to_string()
+}
+
+//mm:ss:ff, there are 75 frames in a second
+fn parse_time(s: &str) -> Option<Duration> {
+    let mut parts = s.split(':').map(|p| p.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(Duration::from_secs(minutes * 60 + seconds) + Duration::from_nanos(frames * 1_000_000_000 / 75))
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    io::Write,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{config::data_dir, replaygain::ReplayGain, track::Track};
+
+//bumped whenever the fields change, an old file is then rebuilt from scratch
+const HEADER: &str = "bwmp library 2";
+
+struct Entry {
+    mtime: u64,
+    size: u64,
+    track: Track,
+}
+
+//everything probing a file found out, kept so startup only has to probe what changed
+pub struct LibraryDb {
+    entries: HashMap<PathBuf, Entry>,
+    changed: bool,
+}
+
+impl LibraryDb {
+    pub fn empty() -> Self {
+        Self { entries: HashMap::new(), changed: true }
+    }
+    //--rescan starts from an empty one, so every file gets probed again
+    pub fn open(rescan: bool) -> Self {
+        if rescan {
+            Self::empty()
+        } else {
+            Self::load()
+        }
+    }
+    pub fn load() -> Self {
+        let Ok(db) = fs::read_to_string(db_path()) else { return Self::empty() };
+        let mut lines = db.lines();
+        if lines.next() != Some(HEADER) {
+            return Self::empty();
+        }
+        let entries = lines
+            .filter_map(parse_entry)
+            .map(|e| (e.track.path.clone(), e))
+            .collect();
+        Self { entries, changed: false }
+    }
+    //the stored track while the file keeps its mtime and size, a freshly probed one otherwise
+    pub fn track(&mut self, path: &Path) -> Track {
+        let Ok(meta) = fs::metadata(path) else { return Track::from_path(path) };
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        let size = meta.len();
+        if let Some(entry) = self.entries.get(path) {
+            if entry.mtime == mtime && entry.size == size {
+                return entry.track.clone();
+            }
+        }
+        let track = Track::from_path(path);
+        self.entries.insert(path.to_path_buf(), Entry { mtime, size, track: track.clone() });
+        self.changed = true;
+        track
+    }
+    //drops files that were under one of the roots but werent found, other roots are left alone
+    pub fn prune(&mut self, roots: &[PathBuf], found: &HashSet<&Path>) {
+        let before = self.entries.len();
+        self.entries
+            .retain(|path, _| found.contains(path.as_path()) || !roots.iter().any(|r| path.starts_with(r)));
+        self.changed |= self.entries.len() != before;
+    }
+    pub fn save(&mut self) -> Result<(), io::Error> {
+        if !self.changed {
+            return Ok(());
+        }
+        fs::create_dir_all(data_dir())?;
+        //written next to the old one and moved over it, so a crash cant leave half a library
+        let tmp = db_path().with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp)?);
+        writeln!(file, "{HEADER}")?;
+        for entry in self.entries.values() {
+            writeln!(file, "{}", format_entry(entry))?;
+        }
+        file.flush()?;
+        fs::rename(tmp, db_path())?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+fn db_path() -> PathBuf {
+    data_dir().join("library")
+}
+
+//path, mtime, size, title, artist, album, track, disc, year, genre, duration, then the four
+//replaygain values, missing ones are empty
+fn format_entry(entry: &Entry) -> String {
+    let t = &entry.track;
+    let rg = &t.replay_gain;
+    let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+    let float = |f: Option<f32>| f.map(|f| f.to_string()).unwrap_or_default();
+    [
+        encode_path(&t.path),
+        entry.mtime.to_string(),
+        entry.size.to_string(),
+        escape(&t.title),
+        t.artist.as_deref().map(escape).unwrap_or_default(),
+        t.album.as_deref().map(escape).unwrap_or_default(),
+        number(t.track_number),
+        number(t.disc),
+        number(t.year),
+        t.genre.as_deref().map(escape).unwrap_or_default(),
+        t.duration.map(|d| d.as_millis().to_string()).unwrap_or_default(),
+        float(rg.track_gain),
+        float(rg.track_peak),
+        float(rg.album_gain),
+        float(rg.album_peak),
+    ]
+    .join("\t")
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let fields: Vec<String> = line.split('\t').map(unescape).collect();
+    let [path, mtime, size, title, artist, album, track_number, disc, year, genre, duration, track_gain, track_peak, album_gain, album_peak] =
+        fields.try_into().ok()?;
+    let text = |s: String| (!s.is_empty()).then_some(s);
+    let mut track = Track::from_filename(&decode_path(&path)?);
+    track.title = title;
+    track.artist = text(artist);
+    track.album = text(album);
+    track.track_number = track_number.parse().ok();
+    track.disc = disc.parse().ok();
+    track.year = year.parse().ok();
+    track.genre = text(genre);
+    track.duration = duration.parse().ok().map(Duration::from_millis);
+    track.replay_gain = ReplayGain {
+        track_gain: track_gain.parse().ok(),
+        track_peak: track_peak.parse().ok(),
+        album_gain: album_gain.parse().ok(),
+        album_peak: album_peak.parse().ok(),
+    };
+    Some(Entry { mtime: mtime.parse().ok()?, size: size.parse().ok()?, track })
+}
+
+//paths dont have to be utf-8, so their bytes are stored with anything that isnt plain ascii
+//as %xx, the escaping below leaves the result alone
+pub(crate) fn encode_path(path: &Path) -> String {
+    let mut out = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'%' && byte != b'\\' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02x}")),
+        }
+    }
+    out
+}
+
+pub(crate) fn decode_path(s: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(PathBuf::from(OsStr::from_bytes(&bytes)))
+}
+
+//tabs and newlines would break the lines apart
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => (),
+        }
+    }
+    out
+}
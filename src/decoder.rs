@@ -0,0 +1,178 @@
+use std::{path::Path, time::Duration};
+
+use rodio::Source;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions},
+    errors::Error,
+    formats::{FormatReader, SeekMode, SeekTo},
+    units::Time,
+};
+
+use crate::{formats, track::Track};
+
+//symphonia decoder wrapped as a rodio source, unlike rodio::Decoder this one can seek
+pub struct TrackDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    duration: Option<Duration>,
+    channels: u16,
+    sample_rate: u32,
+    //interleaved samples of the last decoded packet
+    samples: Vec<f32>,
+    sample_pos: usize,
+    //frames still to be thrown away after an accurate seek landed before the target
+    skip_ts: u64,
+    //where the track starts in the file, only cue tracks dont start at 0
+    start: Duration,
+    end: Option<Duration>,
+    //samples left before end
+    remaining: Option<u64>,
+}
+
+impl TrackDecoder {
+    pub fn new(path: &Path) -> Result<Self, Error> {
+        let probed = formats::probe(path).ok_or(Error::Unsupported("unknown container"))?;
+        let format = probed.format;
+        let track = format.default_track().ok_or(Error::Unsupported("no audio track"))?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or(Error::Unsupported("unknown sample rate"))?;
+        let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(2) as u16;
+        let track_id = track.id;
+        let duration = formats::duration_of(format.as_ref());
+        let mut selfish = Self {
+            format,
+            decoder,
+            track_id,
+            duration,
+            channels,
+            sample_rate,
+            samples: Vec::new(),
+            sample_pos: 0,
+            skip_ts: 0,
+            start: Duration::ZERO,
+            end: None,
+            remaining: None,
+        };
+        //decode the first packet now so the real channel count and rate are known, a file
+        //without a single good packet is as good as unreadable
+        if !selfish.next_packet() {
+            return Err(Error::DecodeError("no playable audio"));
+        }
+        Ok(selfish)
+    }
+    //opens the part of the file a track is about, all of it unless it came from a cue sheet
+    pub fn open(track: &Track) -> Result<Self, Error> {
+        let mut selfish = Self::new(&track.path)?;
+        if let Some(cue) = track.cue {
+            selfish.start = cue.start;
+            selfish.end = cue.end;
+            if let Some(duration) = selfish.duration {
+                selfish.duration = Some(cue.end.unwrap_or(duration).saturating_sub(cue.start));
+            }
+            //a cue track in a file that cant seek would play from the wrong place
+            if !selfish.seek(Duration::ZERO) {
+                return Err(Error::Unsupported("cant seek to the start of the cue track"));
+            }
+        }
+        Ok(selfish)
+    }
+    //jumps to a position, relative to where the track starts, returns false if the container
+    //cant seek
+    pub fn seek(&mut self, to: Duration) -> bool {
+        let to = self.start + to;
+        let time = Time::new(to.as_secs(), to.subsec_nanos() as f64 / 1_000_000_000.0);
+        let seek_to = SeekTo::Time { time, track_id: Some(self.track_id) };
+        match self.format.seek(SeekMode::Accurate, seek_to) {
+            Ok(seeked) => {
+                self.decoder.reset();
+                self.skip_ts = seeked.required_ts;
+                self.samples.clear();
+                self.sample_pos = 0;
+                self.next_packet();
+                if let Some(end) = self.end {
+                    let frames = end.saturating_sub(to).as_nanos() * self.sample_rate as u128 / 1_000_000_000;
+                    self.remaining = Some(frames as u64 * self.channels as u64);
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    //refills the sample buffer, returns false when the track is over
+    fn next_packet(&mut self) -> bool {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(p) => p,
+                Err(Error::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(_) => return false,
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(d) => d,
+                //a single corrupt packet is skipped, the rest of the file can still be fine
+                Err(Error::DecodeError(_)) => continue,
+                Err(_) => return false,
+            };
+            let spec = *decoded.spec();
+            let frames = decoded.frames();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+            self.channels = spec.channels.count() as u16;
+            self.sample_rate = spec.rate;
+            //drop the part of the packet that is before the seek target
+            let skip = self.skip_ts.saturating_sub(packet.ts()).min(frames as u64) as usize;
+            if skip >= frames {
+                continue;
+            }
+            self.skip_ts = 0;
+            self.samples.clear();
+            self.samples.extend_from_slice(&buffer.samples()[skip * self.channels as usize..]);
+            self.sample_pos = 0;
+            return true;
+        }
+    }
+}
+
+impl Iterator for TrackDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.checked_sub(1)?;
+        }
+        let sample = *self.samples.get(self.sample_pos)?;
+        self.sample_pos += 1;
+        //refill right away, a frame length of 0 tells rodio the track is over
+        if self.sample_pos >= self.samples.len() {
+            self.next_packet();
+        }
+        Some(sample)
+    }
+}
+
+impl Source for TrackDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        let left = self.samples.len() - self.sample_pos;
+        Some(self.remaining.map_or(left, |r| left.min(r as usize)))
+    }
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    f32::consts::PI,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+#[derive(Clone, Copy)]
+pub enum BandKind {
+    Peak,
+    LowShelf,
+    HighShelf,
+}
+
+//one biquad, gain in dB
+#[derive(Clone, Copy)]
+pub struct Band {
+    pub kind: BandKind,
+    pub freq: f32,
+    pub gain: f32,
+    pub q: f32,
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            BandKind::Peak => "",
+            BandKind::LowShelf => "<",
+            BandKind::HighShelf => ">",
+        };
+        if self.freq >= 1000.0 {
+            write!(f, "{}{}k {:+}", kind, self.freq / 1000.0, self.gain)
+        } else {
+            write!(f, "{}{} {:+}", kind, self.freq, self.gain)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Preset {
+    pub name: String,
+    pub bands: Vec<Band>,
+}
+
+//the ones you get without writing any into the config
+pub fn default_presets() -> Vec<Preset> {
+    use BandKind::*;
+    vec![
+        Preset {
+            name: "bass".into(),
+            bands: vec![Band { kind: LowShelf, freq: 120.0, gain: 6.0, q: 0.7 }],
+        },
+        Preset {
+            name: "treble".into(),
+            bands: vec![Band { kind: HighShelf, freq: 6000.0, gain: 5.0, q: 0.7 }],
+        },
+        Preset {
+            name: "loudness".into(),
+            bands: vec![
+                Band { kind: LowShelf, freq: 100.0, gain: 5.0, q: 0.7 },
+                Band { kind: Peak, freq: 2500.0, gain: -2.0, q: 1.0 },
+                Band { kind: HighShelf, freq: 8000.0, gain: 4.0, q: 0.7 },
+            ],
+        },
+    ]
+}
+
+//"rock = lowshelf 100 4 0.7, peak 1000 -2 1, highshelf 8000 3 0.7"
+pub fn parse_preset(s: &str) -> Option<Preset> {
+    let (name, bands) = s.split_once('=')?;
+    let bands = bands
+        .split(',')
+        .map(|band| {
+            let mut fields = band.split_whitespace();
+            let kind = match fields.next()? {
+                "peak" => BandKind::Peak,
+                "lowshelf" => BandKind::LowShelf,
+                "highshelf" => BandKind::HighShelf,
+                _ => return None,
+            };
+            let freq = fields.next()?.parse().ok()?;
+            let gain = fields.next()?.parse().ok()?;
+            let q = fields.next().map(|q| q.parse().ok()).unwrap_or(Some(0.7))?;
+            Some(Band { kind, freq, gain, q })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Preset { name: name.trim().to_string(), bands })
+}
+
+struct EqState {
+    enabled: AtomicBool,
+    preset: AtomicUsize,
+    presets: Vec<Preset>,
+}
+
+//shared by the player and every equalizer in the audio thread, so changes apply mid track
+#[derive(Clone)]
+pub struct EqControl(Arc<EqState>);
+
+impl EqControl {
+    //starts off when the preset to start with is None or doesnt exist
+    pub fn new(presets: Vec<Preset>, start: Option<&str>) -> Self {
+        let start = start.and_then(|name| presets.iter().position(|p| p.name == name));
+        Self(Arc::new(EqState {
+            enabled: AtomicBool::new(start.is_some()),
+            preset: AtomicUsize::new(start.unwrap_or(0)),
+            presets,
+        }))
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Relaxed)
+    }
+    pub fn toggle(&self) {
+        if !self.0.presets.is_empty() {
+            self.0.enabled.fetch_xor(true, Ordering::Relaxed);
+        }
+    }
+    pub fn next_preset(&self) {
+        let next = (self.0.preset.load(Ordering::Relaxed) + 1) % self.0.presets.len().max(1);
+        self.0.preset.store(next, Ordering::Relaxed);
+    }
+    pub fn preset(&self) -> Option<&Preset> {
+        self.0.presets.get(self.0.preset.load(Ordering::Relaxed))
+    }
+    //None when off, checked by the equalizer every frame
+    fn active(&self) -> Option<usize> {
+        if self.is_enabled() {
+            Some(self.0.preset.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+}
+
+//normalized biquad coefficients, from the audio eq cookbook
+#[derive(Clone, Copy)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    fn new(band: &Band, sample_rate: u32) -> Self {
+        let a = 10f32.powf(band.gain / 40.0);
+        //keep the filter below nyquist or it blows up
+        let freq = band.freq.min(sample_rate as f32 * 0.45);
+        let w0 = 2.0 * PI * freq / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * band.q.max(0.01));
+        let (b0, b1, b2, a0, a1, a2) = match band.kind {
+            BandKind::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BandKind::LowShelf => {
+                let sq = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos + sq),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - sq),
+                    (a + 1.0) + (a - 1.0) * cos + sq,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - sq,
+                )
+            }
+            BandKind::HighShelf => {
+                let sq = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos + sq),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - sq),
+                    (a + 1.0) - (a - 1.0) * cos + sq,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - sq,
+                )
+            }
+        };
+        Self { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+    }
+}
+
+//transposed direct form 2, one per band and channel
+#[derive(Clone, Copy, Default)]
+struct FilterState {
+    z1: f32,
+    z2: f32,
+}
+
+impl FilterState {
+    fn process(&mut self, c: &Coefficients, x: f32) -> f32 {
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+}
+
+//sits between the decoder and the sink
+pub struct Equalizer<S> {
+    inner: S,
+    control: EqControl,
+    //preset and stream format the filters were built for
+    built_for: Option<(usize, u32, u16)>,
+    coefficients: Vec<Coefficients>,
+    //coefficients.len() * channels, band major
+    states: Vec<FilterState>,
+    channel: usize,
+}
+
+impl<S: Source<Item = f32>> Equalizer<S> {
+    pub fn new(inner: S, control: EqControl) -> Self {
+        Self {
+            inner,
+            control,
+            built_for: None,
+            coefficients: Vec::new(),
+            states: Vec::new(),
+            channel: 0,
+        }
+    }
+    fn rebuild(&mut self) {
+        let active = self.control.active();
+        let format = active.map(|p| (p, self.inner.sample_rate(), self.inner.channels()));
+        if format == self.built_for {
+            return;
+        }
+        self.built_for = format;
+        self.coefficients.clear();
+        if let Some(preset) = active.and_then(|p| self.control.0.presets.get(p)) {
+            let rate = self.inner.sample_rate();
+            self.coefficients.extend(preset.bands.iter().map(|b| Coefficients::new(b, rate)));
+        }
+        self.states = vec![FilterState::default(); self.coefficients.len() * self.inner.channels() as usize];
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Equalizer<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        //settings only change between frames
+        if self.channel == 0 {
+            self.rebuild();
+        }
+        let mut sample = self.inner.next()?;
+        let channels = self.inner.channels().max(1) as usize;
+        for (band, c) in self.coefficients.iter().enumerate() {
+            sample = self.states[band * channels + self.channel].process(c, sample);
+        }
+        self.channel = (self.channel + 1) % channels;
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Equalizer<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
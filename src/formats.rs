@@ -0,0 +1,73 @@
+use std::{fs, path::Path, time::Duration};
+
+use symphonia::core::{
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::{Hint, ProbeResult},
+    units::TimeBase,
+};
+
+//a container/codec combination the decoder can play
+pub struct Format {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+}
+
+//everything rodio can decode with the symphonia backend enabled
+//opus is missing on purpose, symphonia can demux it but has no decoder for it
+pub const FORMATS: &[Format] = &[
+    Format { name: "MP3", extensions: &["mp3", "mp2", "mp1"] },
+    Format { name: "FLAC", extensions: &["flac"] },
+    Format { name: "Ogg Vorbis", extensions: &["ogg", "oga"] },
+    Format { name: "WAV", extensions: &["wav", "wave"] },
+    Format { name: "AIFF", extensions: &["aif", "aiff", "aifc"] },
+    Format { name: "AAC", extensions: &["aac", "adts"] },
+    Format { name: "MP4/ALAC", extensions: &["m4a", "m4b", "mp4"] },
+    Format { name: "Matroska", extensions: &["mka", "webm"] },
+    Format { name: "CAF", extensions: &["caf"] },
+];
+
+//returns the format matching the extension of a path
+pub fn format_of(path: &Path) -> Option<&'static Format> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    FORMATS.iter().find(|f| f.extensions.contains(&ext.as_str()))
+}
+
+pub fn is_supported(path: &Path) -> bool {
+    format_of(path).is_some()
+}
+
+//opens a file and figures out its container, None if symphonia doesnt recognize it
+pub fn probe(path: &Path) -> Option<ProbeResult> {
+    let file = fs::File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    //gapless trims encoder delay and padding, otherwise albums click between tracks
+    let options = FormatOptions { enable_gapless: true, ..Default::default() };
+    symphonia::default::get_probe()
+        .format(&hint, mss, &options, &MetadataOptions::default())
+        .ok()
+}
+
+//gets the length of the default track without decoding it, None if the container doesnt say
+pub fn duration_of(format: &dyn FormatReader) -> Option<Duration> {
+    let track = format.default_track()?;
+    let frames = track.codec_params.n_frames?;
+    let time = match track.codec_params.time_base {
+        Some(tb) => tb.calc_time(frames),
+        None => TimeBase::new(1, track.codec_params.sample_rate?).calc_time(frames),
+    };
+    Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+}
+
+//symphonia needs a xing/info header to know mp3 length, mp3_duration walks the frames instead
+pub fn fallback_duration(path: &Path) -> Option<Duration> {
+    match format_of(path)?.name {
+        "MP3" => mp3_duration::from_path(path).ok(),
+        _ => None,
+    }
+}
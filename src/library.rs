@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config, cue, database::LibraryDb, formats, loudness::LoudnessCache, misc::natural_cmp,
+    playlist_file, track::{Track, TrackId},
+    watcher::Change,
+};
+
+//scans the music dirs or reads the playlist, only files that changed since the last time get
+//their tags read again, also returns the dirs or playlist entries that couldnt be found
+pub fn load_tracks(
+    config: &Config,
+    db: &mut LibraryDb,
+    loudness: &LoudnessCache,
+) -> Result<(Vec<Track>, Vec<String>), io::Error> {
+    let mut missing = Vec::new();
+    let mut tracks = match &config.playlist {
+        Some(playlist) => from_playlist(playlist, db, &mut missing)?,
+        None => {
+            let (sheets, paths): (Vec<PathBuf>, Vec<PathBuf>) =
+                scan(&config.dirs, config.depth, &mut missing)?.into_iter().partition(|p| cue::is_cue_sheet(p));
+            let roots: Vec<PathBuf> = config.dirs.iter().filter_map(|d| fs::canonicalize(d).ok()).collect();
+            db.prune(&roots, &paths.iter().map(|p| p.as_path()).collect());
+            split_cue_sheets(paths.iter().map(|p| db.track(p)).collect(), &sheets)
+        }
+    };
+    //not being able to save only makes the next start slower
+    let _ = db.save();
+    loudness.fill(&mut tracks);
+    Ok((tracks, missing))
+}
+
+//the tracks of a playlist file in its order, entries whose file is gone go into missing
+fn from_playlist(playlist: &Path, db: &mut LibraryDb, missing: &mut Vec<String>) -> Result<Vec<Track>, io::Error> {
+    //how many times every file came up so far
+    let mut seen: HashMap<PathBuf, u32> = HashMap::new();
+    let mut tracks = Vec::new();
+    for entry in playlist_file::load(playlist)? {
+        let Ok(path) = fs::canonicalize(&entry.path) else {
+            missing.push(entry.path.display().to_string());
+            continue;
+        };
+        let mut track = db.track(&path);
+        //ids come from paths, a file listed twice would otherwise be the same track twice
+        let repeat = seen.entry(path.clone()).or_insert(0);
+        if *repeat > 0 {
+            track.id = TrackId::of_repeat(&path, *repeat);
+        }
+        *repeat += 1;
+        entry.apply_to(&mut track);
+        tracks.push(track);
+    }
+    if tracks.is_empty() {
+        let message = format!("nothing to play in {}", playlist.display());
+        return Err(io::Error::new(io::ErrorKind::NotFound, message));
+    }
+    Ok(tracks)
+}
+
+//swaps every file a cue sheet points at for the songs in it
+fn split_cue_sheets(tracks: Vec<Track>, sheets: &[PathBuf]) -> Vec<Track> {
+    let by_path: HashMap<&Path, usize> = tracks.iter().enumerate().map(|(i, t)| (t.path.as_path(), i)).collect();
+    let by_stem: HashMap<(Option<&Path>, Option<&OsStr>), usize> =
+        tracks.iter().enumerate().map(|(i, t)| ((t.path.parent(), t.path.file_stem()), i)).collect();
+    let stem_of = |p: &Path| (p.parent().map(|p| p.to_path_buf()), p.file_stem().map(|s| s.to_os_string()));
+    let mut splits: HashMap<usize, Vec<Track>> = HashMap::new();
+    for sheet_path in sheets {
+        let sheets = cue::parse(sheet_path);
+        let single = sheets.len() == 1;
+        for sheet in sheets {
+            //FILE often still names the wav it was ripped from, a flac with the same name next
+            //to it, or one named like the sheet, is what gets played then
+            let mut candidates = vec![stem_of(&sheet.file)];
+            if single {
+                candidates.push(stem_of(sheet_path));
+            }
+            let found = by_path.get(sheet.file.as_path()).copied().or_else(|| {
+                candidates.iter().find_map(|(dir, stem)| by_stem.get(&(dir.as_deref(), stem.as_deref())).copied())
+            });
+            let Some(index) = found else { continue };
+            let songs = sheet.split(&tracks[index]);
+            if !songs.is_empty() {
+                splits.insert(index, songs);
+            }
+        }
+    }
+    tracks
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, t)| splits.remove(&i).unwrap_or_else(|| vec![t]))
+        .collect()
+}
+
+//applies what the watcher saw to the list, returns the new id of every track that was renamed
+pub fn apply_changes(
+    tracks: &mut Vec<Track>,
+    changes: Vec<Change>,
+    db: &mut LibraryDb,
+    loudness: &LoudnessCache,
+) -> HashMap<TrackId, TrackId> {
+    let mut renamed = HashMap::new();
+    for change in changes {
+        match change {
+            Change::Added(path) => {
+                //the songs of a cue sheet stay as they are, the sheet is only read at startup
+                if tracks.iter().any(|t| t.path == path && t.cue.is_some()) {
+                    continue;
+                }
+                let track = db.track(&path);
+                match tracks.iter_mut().find(|t| t.path == path) {
+                    Some(t) => *t = track,
+                    None => tracks.push(track),
+                }
+            }
+            Change::Removed(path) => tracks.retain(|t| !t.path.starts_with(&path)),
+            Change::Renamed(from, to) => {
+                for t in tracks.iter_mut().filter(|t| t.path.starts_with(&from)) {
+                    //joining an empty path would leave a trailing slash
+                    let path = match t.path.strip_prefix(&from) {
+                        Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                        _ => to.clone(),
+                    };
+                    let track = match t.cue {
+                        Some(cue) => Track { id: TrackId::of_cue(&path, cue.number), path, ..t.clone() },
+                        None => Track { broken: t.broken, ..db.track(&path) },
+                    };
+                    //renamed twice in one go still leads from the first id to the last
+                    for id in renamed.values_mut().filter(|id| **id == t.id) {
+                        *id = track.id;
+                    }
+                    renamed.insert(t.id, track.id);
+                    *t = track;
+                }
+            }
+        }
+    }
+    tracks.sort_by(|a, b| compare_paths(&a.path, &b.path));
+    tracks.dedup_by(|a, b| a.id == b.id);
+    let _ = db.save();
+    loudness.fill(tracks);
+    renamed
+}
+
+//walks every root up to max_depth directories deep and returns the playable files and cue
+//sheets, sorted, roots that dont exist go into missing and the others are still scanned
+pub fn scan(roots: &[PathBuf], max_depth: usize, missing: &mut Vec<String>) -> Result<Vec<PathBuf>, io::Error> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    //(device, inode) of every directory already walked, stops symlink loops and overlapping roots
+    let mut visited: HashSet<(u64, u64)> = HashSet::new();
+    for root in roots {
+        //absolute paths, so caches and saved state dont depend on where bwmp was started from
+        let (Ok(root), Ok(meta)) = (fs::canonicalize(root), fs::metadata(root)) else {
+            missing.push(root.display().to_string());
+            continue;
+        };
+        if meta.is_dir() {
+            walk(&root, max_depth, &mut visited, &mut paths);
+        } else if formats::is_supported(&root) {
+            paths.push(root);
+        }
+    }
+    paths.sort_by(|a, b| compare_paths(a, b));
+    paths.dedup();
+    if !paths.iter().any(|p| formats::is_supported(p)) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no music files found!"));
+    }
+    Ok(paths)
+}
+
+fn walk(dir: &Path, depth: usize, visited: &mut HashSet<(u64, u64)>, paths: &mut Vec<PathBuf>) {
+    //fs::metadata follows symlinks, so a link back up the tree has the same inode as its target
+    let Ok(meta) = fs::metadata(dir) else { return };
+    if !visited.insert((meta.dev(), meta.ino())) {
+        return;
+    }
+    //unreadable directories are skipped instead of failing the whole scan
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = fs::metadata(&path) else { continue };
+        if meta.is_dir() {
+            if depth > 0 {
+                walk(&path, depth - 1, visited, paths);
+            }
+        } else if formats::is_supported(&path) || cue::is_cue_sheet(&path) {
+            paths.push(path);
+        }
+    }
+}
+
+//compares component by component so "Album/" sorts before "Album 2/"
+fn compare_paths(a: &Path, b: &Path) -> std::cmp::Ordering {
+    let mut a_iter = a.iter();
+    let mut b_iter = b.iter();
+    loop {
+        match (a_iter.next(), b_iter.next()) {
+            (Some(x), Some(y)) => {
+                let ord = natural_cmp(&x.to_string_lossy(), &y.to_string_lossy());
+                if ord.is_ne() {
+                    return ord;
+                }
+            }
+            (x, y) => return x.is_some().cmp(&y.is_some()),
+        }
+    }
+}
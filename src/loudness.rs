@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::UNIX_EPOCH,
+};
+
+use ebur128::{EbuR128, Mode};
+use rodio::Source;
+
+use crate::{
+    config::{cache_dir, Config},
+    database::{decode_path, encode_path},
+    decoder::TrackDecoder,
+    formats, library,
+    track::Track,
+};
+
+//replaygain 2.0 aims for -18 LUFS
+const REFERENCE_LUFS: f64 = -18.0;
+
+//what the scanner measured for one file
+#[derive(Clone, Copy)]
+struct Measurement {
+    mtime: u64,
+    //integrated loudness in LUFS
+    loudness: f64,
+    //true peak as a linear sample value
+    peak: f64,
+}
+
+//measurements for files without gain tags, kept in the cache dir so the audio files are never written to
+pub struct LoudnessCache {
+    entries: HashMap<PathBuf, Measurement>,
+}
+
+impl LoudnessCache {
+    pub fn load() -> Self {
+        let mut entries = HashMap::new();
+        if let Ok(cache) = fs::read_to_string(cache_path()) {
+            for line in cache.lines() {
+                //the path goes last since it is the only field that can contain anything
+                let mut fields = line.splitn(4, '\t');
+                let (Some(mtime), Some(loudness), Some(peak), Some(path)) =
+                    (fields.next(), fields.next(), fields.next(), fields.next())
+                else {
+                    continue;
+                };
+                let (Ok(mtime), Ok(loudness), Ok(peak), Some(path)) =
+                    (mtime.parse(), loudness.parse(), peak.parse(), decode_path(path))
+                else {
+                    continue;
+                };
+                entries.insert(path, Measurement { mtime, loudness, peak });
+            }
+        }
+        Self { entries }
+    }
+    fn save(&self) -> Result<(), io::Error> {
+        fs::create_dir_all(cache_dir())?;
+        //written next to the old one and moved over it, like the library
+        let tmp = cache_path().with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp)?);
+        for (path, m) in &self.entries {
+            //encoded the same way as in the library, non utf-8 names would never match otherwise
+            writeln!(file, "{}\t{}\t{}\t{}", m.mtime, m.loudness, m.peak, encode_path(path))?;
+        }
+        file.flush()?;
+        fs::rename(tmp, cache_path())
+    }
+    //gives untagged tracks the gain the scanner worked out, as long as the file hasnt changed since
+    pub fn fill(&self, tracks: &mut [Track]) {
+        for track in tracks.iter_mut() {
+            //the measurement is of the whole file, not of one song on a cue sheet
+            if track.replay_gain.track_gain.is_some() || track.cue.is_some() {
+                continue;
+            }
+            let Some(m) = self.entries.get(&track.path) else { continue };
+            if mtime_of(&track.path) != Some(m.mtime) {
+                continue;
+            }
+            track.replay_gain.track_gain = Some((REFERENCE_LUFS - m.loudness) as f32);
+            track.replay_gain.track_peak = Some(m.peak as f32);
+        }
+    }
+}
+
+fn cache_path() -> PathBuf {
+    cache_dir().join("loudness")
+}
+
+fn mtime_of(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+//decodes the whole file and runs it through an EBU R128 meter
+fn measure(path: &Path) -> Option<Measurement> {
+    let mtime = mtime_of(path)?;
+    let mut decoder = TrackDecoder::new(path).ok()?;
+    let channels = decoder.channels() as u32;
+    let mut meter = EbuR128::new(channels, decoder.sample_rate(), Mode::I | Mode::TRUE_PEAK).ok()?;
+    let mut chunk: Vec<f32> = Vec::with_capacity(channels as usize * 4096);
+    loop {
+        chunk.clear();
+        chunk.extend(decoder.by_ref().take(channels as usize * 4096));
+        //a half frame at the very end of a broken file would upset the meter
+        chunk.truncate(chunk.len() - chunk.len() % channels as usize);
+        if chunk.is_empty() {
+            break;
+        }
+        meter.add_frames_f32(&chunk).ok()?;
+    }
+    let loudness = meter.loudness_global().ok()?;
+    //silent files have no integrated loudness
+    if !loudness.is_finite() {
+        return None;
+    }
+    let peak = (0..channels)
+        .filter_map(|c| meter.true_peak(c).ok())
+        .fold(0.0, f64::max);
+    Some(Measurement { mtime, loudness, peak })
+}
+
+//the `bwmp scan-loudness` mode, measures every untagged file that isnt cached yet
+pub fn scan_library(config: &Config) {
+    let mut missing = Vec::new();
+    let paths = match library::scan(&config.dirs, config.depth, &mut missing) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    for root in missing {
+        eprintln!("couldnt find {root}");
+    }
+    let cache = LoudnessCache::load();
+    let todo: Vec<PathBuf> = paths
+        .into_iter()
+        .filter(|p| formats::is_supported(p))
+        .filter(|p| match cache.entries.get(p) {
+            Some(m) => mtime_of(p) != Some(m.mtime),
+            None => true,
+        })
+        .filter(|p| Track::from_path(p).replay_gain.track_gain.is_none())
+        .collect();
+    println!("{} files to scan", todo.len());
+    let cache = Mutex::new(cache);
+    let next = AtomicUsize::new(0);
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = todo.get(i) else { break };
+                match measure(path) {
+                    Some(m) => {
+                        println!("[{}/{}] {:.1} LUFS {}", i + 1, todo.len(), m.loudness, path.display());
+                        cache.lock().unwrap().entries.insert(path.clone(), m);
+                    }
+                    None => println!("[{}/{}] couldnt measure {}", i + 1, todo.len(), path.display()),
+                }
+            });
+        }
+    });
+    if let Err(e) = cache.into_inner().unwrap().save() {
+        eprintln!("couldnt save the loudness cache: {e}");
+        std::process::exit(1);
+    }
+}
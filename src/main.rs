@@ -1,37 +1,108 @@
-use std::io::stdout;
-use std::time::Instant;
+use std::{
+    io::{stdout, Stdout},
+    sync::OnceLock,
+};
 
 use termion::event::Key;
 use termion::input::TermRead;
-use termion::raw::IntoRawMode;
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use ui::Panel;
 
 mod colors;
+mod config;
+mod cue;
+mod database;
+mod decoder;
+mod eq;
+mod formats;
+mod library;
+mod loudness;
 mod misc;
+mod output;
+mod pcm;
 mod player;
+mod playlist;
+mod playlist_file;
+mod replaygain;
+mod session;
+mod source;
+mod stretch;
+mod track;
 mod ui;
+mod watcher;
+
+//kept out here so sepuku can leave raw mode from anywhere, the panic hook included
+static TERMINAL: OnceLock<RawTerminal<Stdout>> = OnceLock::new();
 
 fn sepuku() {
     misc::clear_term();
     print!("{}", termion::cursor::Show);
+    if let Some(terminal) = TERMINAL.get() {
+        let _ = terminal.suspend_raw_mode();
+    }
+}
+
+//what the keyboard is currently typing into
+enum Mode {
+    Normal,
+    Search,
+    Goto,
+    //typing a file name, true saves the search matches instead of the queue
+    Save(bool),
 }
 
 fn main() {
     //temp
+    if std::env::args().nth(1).as_deref() == Some("scan-loudness") {
+        loudness::scan_library(&misc::config_from_args());
+        return;
+    }
+
+    let config = misc::config_from_args();
+    if let Some(path) = &config.pcm_out {
+        pcm::run(&config, path);
+        return;
+    }
+    let output = match output::open(&config.backend, config.device.as_deref(), &config.wav) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
     //probably permanent
-    let _stdout = stdout().into_raw_mode().unwrap();
-    let mut player: player::Player = misc::construct_player_from_args();
-    let mut search_mode: bool = false;
+    let _ = TERMINAL.set(stdout().into_raw_mode().unwrap());
+    //a panic would otherwise leave the terminal raw and the message all over the ui
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        sepuku();
+        default_hook(info);
+    }));
+    let mut player = player::Player::new(&config, output);
+    let mut mode = Mode::Normal;
     let mut search_string = String::new();
     print!("{}", termion::cursor::Hide);
     let mut stdin = termion::async_stdin().keys();
-    player.play(0);
+    if !(config.resume && player.resume(&config)) {
+        player.play(player.playlist.index_at(0));
+    }
     loop {
-        let now = Instant::now();
         let input = stdin.next();
         if let Some(Ok(key)) = input {
-            if !search_mode {
-                match key {
+            match mode {
+                Mode::Normal => match key {
+                    //the queue and history panels take over the list keys while they are shown
+                    Key::Up if player.ui.panel() != Panel::List => player.select_in_panel(-1),
+                    Key::Down if player.ui.panel() != Panel::List => player.select_in_panel(1),
+                    Key::Char('\n') if player.ui.panel() == Panel::Queue => player.jump_to_queued(),
+                    Key::Char('\n') if player.ui.panel() == Panel::History => player.play_from_history(),
+                    Key::Char('d') if player.ui.panel() == Panel::Queue => player.remove_queued(),
+                    Key::Char('K') if player.ui.panel() == Panel::Queue => player.move_queued(-1),
+                    Key::Char('J') if player.ui.panel() == Panel::Queue => player.move_queued(1),
+                    Key::Char('c') if player.ui.panel() == Panel::Queue => player.clear_queue(),
+                    Key::Char('\t') => player.ui.next_panel(),
                     Key::Char(' ') => player.toggle_pause(),
                     Key::Char('+') => player.change_volume(player.volume() + 0.1),
                     Key::Char('-') => player.change_volume(player.volume() - 0.1),
@@ -41,18 +112,31 @@ fn main() {
                     Key::Char('s') => player.toggle_shuffle(),
                     Key::Char('\n') => player.play_selected(),
                     Key::Char('a') => player.put_selected_in_queue(),
-                    Key::Char('/') => {player.ui.display_searching(""); search_mode = true},
+                    Key::Char('A') => player.play_selected_next(),
+                    Key::Char('e') => player.toggle_eq(),
+                    Key::Char('E') => player.next_eq_preset(),
+                    Key::Char('[') => player.change_speed(player.speed() - 0.1),
+                    Key::Char(']') => player.change_speed(player.speed() + 0.1),
+                    Key::Char('\\') => player.change_speed(1.0),
+                    Key::Char('/') => {player.ui.display_searching(""); mode = Mode::Search},
+                    Key::Char('g') => {player.ui.display_prompt("Go to", ""); mode = Mode::Goto},
+                    Key::Char('w') => {player.ui.display_prompt("Save queue as", ""); mode = Mode::Save(false)},
+                    Key::Char('W') => {player.ui.display_prompt("Save matches as", ""); mode = Mode::Save(true)},
+                    Key::Left => player.seek_relative(-5),
+                    Key::Right => player.seek_relative(5),
+                    Key::Char('<') => player.seek_relative(-30),
+                    Key::Char('>') => player.seek_relative(30),
+                    Key::Char(c @ '0'..='9') => player.seek_percent(c.to_digit(10).unwrap() * 10),
                     Key::Up => player.ui.select_previous_track(),
                     Key::Down => player.ui.select_next_track(),
                     Key::Char('q') => break,
                     _ => (),
-                }
-            } else {
-                match key {
+                },
+                Mode::Search => match key {
                     Key::Char('\n') | Key::Esc => {
                         search_string.clear();
-                        player.ui.update_queue(&player.queue);
-                        search_mode = false
+                        player.show_queue();
+                        mode = Mode::Normal;
                     }
                     Key::Char(char) => {
                         search_string.push(char);
@@ -64,16 +148,61 @@ fn main() {
                         player.search_for(&search_string);
                     }
                     _ => (),
+                },
+                //typing a timestamp like 1:23, enter jumps there
+                Mode::Goto => match key {
+                    Key::Char('\n') | Key::Esc => {
+                        if key == Key::Char('\n') {
+                            if let Some(to) = misc::parse_time(&search_string) {
+                                player.seek(to);
+                            }
+                        }
+                        search_string.clear();
+                        player.show_queue();
+                        mode = Mode::Normal;
+                    }
+                    Key::Char(char @ ('0'..='9' | ':')) => {
+                        search_string.push(char);
+                        player.ui.display_prompt("Go to", &search_string);
+                    }
+                    Key::Backspace => {
+                        search_string.pop();
+                        player.ui.display_prompt("Go to", &search_string);
+                    }
+                    _ => (),
+                },
+                Mode::Save(matching) => {
+                    let prompt = if matching { "Save matches as" } else { "Save queue as" };
+                    match key {
+                        Key::Char('\n') | Key::Esc => {
+                            if key == Key::Char('\n') {
+                                player.save_playlist(&search_string, matching);
+                            }
+                            search_string.clear();
+                            player.show_queue();
+                            mode = Mode::Normal;
+                        }
+                        Key::Char(char) => {
+                            search_string.push(char);
+                            player.ui.display_prompt(prompt, &search_string);
+                        }
+                        Key::Backspace => {
+                            search_string.pop();
+                            player.ui.display_prompt(prompt, &search_string);
+                        }
+                        _ => (),
+                    }
                 }
             }
         }
-        std::thread::sleep(std::time::Duration::from_millis(50));
-        if player.is_finished() {
-            player.play_next(false);
-        }
+        player.wait(std::time::Duration::from_millis(50));
+        player.update();
+        player.check_library();
+        player.autosave_session();
         if player.is_playing() {
-            player.update_bar(now.elapsed());
+            player.update_bar();
         }
     }
+    let _ = player.save_session();
     sepuku();
 }
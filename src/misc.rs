@@ -1,16 +1,55 @@
-use std::env;
+use std::{cmp::Ordering, env, path::Path, time::Duration};
 
-use crate::{player::{self, Repeat}, sepuku};
+use crate::{
+    config::{expand_home, parse_secs, Config},
+    output::{self, match_backend, BackendKind},
+    playlist_file,
+    pcm::match_pcm_format,
+    player::{self, Repeat, Shuffle},
+    replaygain::match_gain_mode,
+    sepuku,
+};
 
 fn help() {
     println!("bwmp - badly written music player\r\n
         usage - bwmp -d <dir> <options> \r\n
+                bwmp <playlist> <options> - play an m3u, m3u8, pls or xspf playlist instead of a dir\r\n
+                bwmp scan-loudness -d <dir> - measure files without replaygain tags\r\n
+                bwmp --list-devices - show the output devices -o accepts\r\n
+                bwmp --pcm-out <file> -d <dir> - write raw pcm instead of playing, - is stdout\r\n
         Options: \r\n
-            -d - specify your music directory, default is current dir\r\n
-            -s - toggle shuffle\r\n
+            -d - specify your music directory, default is current dir, can be repeated\r\n
+            -p <file> - play an m3u, m3u8, pls or xspf playlist\r\n
+            -o <device> - play through this output device instead of the default one\r\n
+            -b <backend> - device (default), null - play to nowhere, file - record to a wav\r\n
+            -w <file> - record what plays into this wav file instead of playing it, implies -b file\r\n
+            --pcm-format <format> - s16le (default) or f32le, for --pcm-out\r\n
+            --pcm-rate <hz> - sample rate for --pcm-out, default is 44100, always 2 channels\r\n
+            -g <mode> - replaygain, off, track, album or auto (track when shuffling tracks, album otherwise)\r\n
+            --preamp <dB> - added on top of the replaygain value\r\n
+            --no-clip-prevention - let replaygain push peaks over full scale\r\n
+            -D <depth> - how many directories deep to look for music, default is 16\r\n
+            --rescan - read the tags of every file again instead of using the library database\r\n
+            --no-resume - start from the top instead of where the last session left off\r\n
+            -s - shuffle tracks\r\n
+            --shuffle <mode> - off, tracks, albums (albums in a random order, each one in order) or\r\n
+                spread (tracks, keeping the same artist from playing twice in a row)\r\n
             -r <mode>, n - repeat none, s - repeat single, a - repeat all\r\n
             -v <0-200>, volume\r\n
-            -h - display this help message\r\n");
+            -c <seconds> - crossfade between tracks, default is 0 (off)\r\n
+            -f <seconds> - fade used when skipping tracks by hand, default is 0 (off)\r\n
+            --speed <0.5-3> - playback speed, the pitch stays the same\r\n
+            -e <preset> - start with the equalizer on, bass, treble, loudness or one from the config\r\n
+            -h - display this help message\r\n
+        Keys: \r\n
+            left/right - seek 5s, </> - seek 30s, 0-9 - jump to 0-90%, g - go to a timestamp\r\n
+            s - shuffle off, tracks, albums or spread\r\n
+            e - equalizer on/off, E - next equalizer preset\r\n
+            [/] - slower/faster, \\ - normal speed\r\n
+            w - save the queue as a playlist, W - save what matches the last search\r\n
+            a - add to the queue, A - play next, tab - show the queue, again for the history\r\n
+            p - back to what played before, enter in the history plays it again\r\n
+            in the queue: enter - play now, d - remove, K/J - move up/down, c - clear\r\n");
 }
 
 fn match_repeat(string: &str) -> Option<Repeat> {
@@ -22,6 +61,16 @@ fn match_repeat(string: &str) -> Option<Repeat> {
     }
 }
 
+fn match_shuffle(string: &str) -> Option<Shuffle> {
+    match string {
+        "off" => Some(Shuffle::Off),
+        "tracks" => Some(Shuffle::Tracks),
+        "albums" => Some(Shuffle::Albums),
+        "spread" => Some(Shuffle::Spread),
+        _ => None,
+    }
+}
+
 fn match_volume(v: &str) -> f32 {
     match v.parse::<f32>() {
         Ok(vol) => vol / 100.0,
@@ -29,32 +78,97 @@ fn match_volume(v: &str) -> f32 {
     }
 }
 
-//reads arguments and applies them to a new player instance
-pub fn construct_player_from_args() -> player::Player {
+//reads the config file and lets the arguments override it
+pub fn config_from_args() -> Config {
     let args: Vec<String> = env::args().collect();
     let mut arg_counter = 1;
-    let mut repeat = Repeat::None;
-    let mut shuffle = false;
-    let mut path = ".";
-    let mut vol = 1.0;
+    let mut config = Config::new_from_config().unwrap_or(Config::new_default());
+    let mut dirs = Vec::new();
     while arg_counter < args.len() {
         match args[arg_counter].as_str() {
             "-h" => {help(); sepuku(); std::process::exit(0);},
+            "--list-devices" => {output::print_devices(); std::process::exit(0);},
+            "-o" => {arg_counter += 1; config.device = Some(args[arg_counter].clone())},
+            "-b" => {
+                arg_counter += 1;
+                config.backend = match_backend(&args[arg_counter]).unwrap_or_default();
+            }
+            "--pcm-out" => {arg_counter += 1; config.pcm_out = Some(args[arg_counter].clone().into())},
+            "--pcm-format" => {
+                arg_counter += 1;
+                config.pcm_format = match_pcm_format(&args[arg_counter]).unwrap_or_default();
+            }
+            "--pcm-rate" => {
+                arg_counter += 1;
+                config.pcm_rate = args[arg_counter].parse().unwrap_or(config.pcm_rate);
+            }
+            "-w" => {
+                arg_counter += 1;
+                config.wav = expand_home(&args[arg_counter]);
+                config.backend = BackendKind::File;
+            }
             "-r" => {
                 arg_counter += 1;
-                repeat = match_repeat(&args[arg_counter]).unwrap_or_default();
+                config.repeat = match_repeat(&args[arg_counter]).unwrap_or_default();
+                config.repeat_given = true;
+            }
+            "-s" => {config.shuffle = Shuffle::Tracks; config.shuffle_given = true},
+            "--shuffle" => {
+                arg_counter += 1;
+                config.shuffle = match_shuffle(&args[arg_counter]).unwrap_or_default();
+                config.shuffle_given = true;
             }
-            "-s" => shuffle = true,
             "-v" => {
                 arg_counter += 1;
-                vol = match_volume(&args[arg_counter]);
+                config.volume = match_volume(&args[arg_counter]);
+                config.volume_given = true;
+            }
+            "-d" => {arg_counter += 1; dirs.push(expand_home(&args[arg_counter]))},
+            "-p" => {arg_counter += 1; config.playlist = Some(expand_home(&args[arg_counter]))},
+            "-c" => {
+                arg_counter += 1;
+                config.crossfade = parse_secs(&args[arg_counter]).unwrap_or(config.crossfade);
+            }
+            "-f" => {
+                arg_counter += 1;
+                config.skip_fade = parse_secs(&args[arg_counter]).unwrap_or(config.skip_fade);
+            }
+            "-g" => {
+                arg_counter += 1;
+                config.gain_mode = match_gain_mode(&args[arg_counter]).unwrap_or_default();
+            }
+            "--preamp" => {
+                arg_counter += 1;
+                config.preamp = args[arg_counter].parse().unwrap_or(config.preamp);
             }
-            "-d" => {arg_counter += 1; path = &args[arg_counter]},
+            "--speed" => {
+                arg_counter += 1;
+                config.speed = args[arg_counter].parse().unwrap_or(config.speed);
+            }
+            "-e" => {
+                arg_counter += 1;
+                config.eq = Some(args[arg_counter].clone());
+            }
+            "--rescan" => config.rescan = true,
+            "--no-resume" => config.resume = false,
+            "--no-clip-prevention" => config.prevent_clipping = false,
+            "-D" => {
+                arg_counter += 1;
+                config.depth = args[arg_counter].parse().unwrap_or(config.depth);
+            }
+            arg if playlist_file::is_playlist(Path::new(arg)) => config.playlist = Some(expand_home(arg)),
             _ => (),
         }
         arg_counter += 1;
     }
-    player::Player::new(&path, repeat, shuffle, vol)
+    //dirs given as arguments replace the ones from the config
+    if !dirs.is_empty() {
+        config.dirs = dirs;
+    }
+    if config.dirs.is_empty() {
+        config.dirs.push(".".into());
+    }
+    config
 }
 
 //clears the terminal
@@ -64,7 +178,7 @@ pub fn clear_term() {
 }
 
 pub fn cut_string(s: String, limit: usize) -> String {
-    let new_string = s.replace(".mp3", "");
+    let new_string = s;
     if limit >= new_string.len() { return new_string }
     //new_string.chars().take(limit).collect()
     //fucking unicode istg
@@ -82,3 +196,64 @@ pub fn cut_string(s: String, limit: usize) -> String {
     }
     new_string[0..byte_end].to_string()
 }
+
+//compares strings the way a human would, "track 2" comes before "track 10"
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_iter = a.chars().peekable();
+    let mut b_iter = b.chars().peekable();
+    //only used when the strings differ by case alone
+    let mut case_ord = Ordering::Equal;
+    loop {
+        match (a_iter.peek().copied(), b_iter.peek().copied()) {
+            (None, None) => return case_ord,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_num = take_number(&mut a_iter);
+                let y_num = take_number(&mut b_iter);
+                //compare by length first so numbers longer than u64 still work
+                let ord = x_num
+                    .len()
+                    .cmp(&y_num.len())
+                    .then_with(|| x_num.cmp(&y_num));
+                if ord.is_ne() {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_lowercase().cmp(y.to_lowercase());
+                if ord.is_ne() {
+                    return ord;
+                }
+                case_ord = case_ord.then(x.cmp(&y));
+                a_iter.next();
+                b_iter.next();
+            }
+        }
+    }
+}
+
+//reads a run of digits without the leading zeroes
+fn take_number(iter: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut num = String::new();
+    while let Some(c) = iter.next_if(|c| c.is_ascii_digit()) {
+        if !(num.is_empty() && c == '0') {
+            num.push(c);
+        }
+    }
+    num
+}
+
+//parses "83", "1:23" or "1:01:23" into a duration
+pub fn parse_time(s: &str) -> Option<Duration> {
+    let mut secs: u64 = 0;
+    let mut parts = 0;
+    for part in s.trim().split(':') {
+        secs = secs.checked_mul(60)? + part.parse::<u64>().ok()?;
+        parts += 1;
+    }
+    if parts > 3 {
+        return None;
+    }
+    Some(Duration::from_secs(secs))
+}
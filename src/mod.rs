@@ -1,5 +1,23 @@
 pub mod colors;
+pub mod config;
+pub mod cue;
+pub mod database;
+pub mod decoder;
+pub mod eq;
+pub mod formats;
+pub mod library;
+pub mod loudness;
 pub mod misc;
+pub mod output;
+pub mod pcm;
 pub mod player;
+pub mod playlist;
+pub mod playlist_file;
+pub mod replaygain;
+pub mod session;
+pub mod source;
+pub mod stretch;
+pub mod track;
 pub mod ui;
+pub mod watcher;
 //pub mod
@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rodio::{
+    cpal::{self, traits::HostTrait},
+    dynamic_mixer::{self, DynamicMixerController},
+    queue::SourcesQueueOutput,
+    DeviceTrait, OutputStream, OutputStreamHandle,
+};
+
+//what the backends without a device run at
+const CHANNELS: u16 = 2;
+const SAMPLE_RATE: u32 = 44100;
+
+//where the sinks of the player end up
+pub trait Backend {
+    //starts playing the output of a sink, alongside whatever else is playing
+    fn play(&self, sink_output: SourcesQueueOutput<f32>);
+}
+
+#[derive(Clone, Default)]
+pub enum BackendKind {
+    #[default] Device,
+    Null,
+    File,
+}
+
+pub fn match_backend(string: &str) -> Option<BackendKind> {
+    match string {
+        "device" => Some(BackendKind::Device),
+        "null" => Some(BackendKind::Null),
+        "file" => Some(BackendKind::File),
+        _ => None,
+    }
+}
+
+//a real sound card through cpal
+pub struct Device {
+    _stream: OutputStream, //must not be freed
+    handle: OutputStreamHandle,
+}
+
+impl Backend for Device {
+    fn play(&self, sink_output: SourcesQueueOutput<f32>) {
+        //only fails once the stream is gone, and then there is nothing to play to anyway
+        let _ = self.handle.play_raw(sink_output);
+    }
+}
+
+//throws the samples away, at the speed a sound card would take them
+pub struct Null {
+    mixer: Arc<DynamicMixerController<f32>>,
+}
+
+impl Backend for Null {
+    fn play(&self, sink_output: SourcesQueueOutput<f32>) {
+        self.mixer.add(sink_output);
+    }
+}
+
+//renders what would have played into a wav file, in real time
+pub struct WavFile {
+    mixer: Arc<DynamicMixerController<f32>>,
+}
+
+impl Backend for WavFile {
+    fn play(&self, sink_output: SourcesQueueOutput<f32>) {
+        self.mixer.add(sink_output);
+    }
+}
+
+//opens the backend the config asks for
+pub fn open(kind: &BackendKind, device: Option<&str>, wav: &Path) -> Result<Box<dyn Backend>, String> {
+    Ok(match kind {
+        BackendKind::Device => {
+            let (stream, handle) = open_device(device)?;
+            Box::new(Device { _stream: stream, handle })
+        }
+        BackendKind::Null => Box::new(Null { mixer: drain(|_| Ok(())) }),
+        BackendKind::File => {
+            let mut writer = WavWriter::create(wav)
+                .map_err(|e| format!("couldnt create {}: {e}", wav.display()))?;
+            Box::new(WavFile { mixer: drain(move |samples| writer.write(samples)) })
+        }
+    })
+}
+
+//mixes every sink together on a thread that pulls samples as fast as they would play,
+//so positions, fades and track changes all happen like they would with a sound card
+fn drain<F>(mut consume: F) -> Arc<DynamicMixerController<f32>>
+where
+    F: FnMut(&[f32]) -> Result<(), io::Error> + Send + 'static,
+{
+    let (controller, mut mixer) = dynamic_mixer::mixer::<f32>(CHANNELS, SAMPLE_RATE);
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut frames_done: u64 = 0;
+        let mut buffer = Vec::new();
+        let mut failed = false;
+        loop {
+            std::thread::sleep(Duration::from_millis(10));
+            let frames_due = (start.elapsed().as_secs_f64() * SAMPLE_RATE as f64) as u64;
+            buffer.clear();
+            //the mixer counts every call to keep the channels lined up, even when nothing plays
+            let samples = (frames_due - frames_done) as usize * CHANNELS as usize;
+            buffer.extend((0..samples).map(|_| mixer.next().unwrap_or(0.0)));
+            frames_done = frames_due;
+            //a full disk shouldnt stop the music, just the recording
+            if !failed {
+                failed = consume(&buffer).is_err();
+            }
+        }
+    });
+    controller
+}
+
+//16 bit pcm wav, the sizes in the header are patched after every write so the file is
+//playable even if bwmp gets killed
+struct WavWriter {
+    file: io::BufWriter<fs::File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path) -> Result<Self, io::Error> {
+        let mut writer = Self { file: io::BufWriter::new(fs::File::create(path)?), data_len: 0 };
+        let block_align = CHANNELS * 2;
+        let header = [
+            &b"RIFF"[..],
+            &36u32.to_le_bytes(),
+            b"WAVE",
+            b"fmt ",
+            &16u32.to_le_bytes(),
+            &1u16.to_le_bytes(),
+            &CHANNELS.to_le_bytes(),
+            &SAMPLE_RATE.to_le_bytes(),
+            &(SAMPLE_RATE * block_align as u32).to_le_bytes(),
+            &block_align.to_le_bytes(),
+            &16u16.to_le_bytes(),
+            b"data",
+            &0u32.to_le_bytes(),
+        ];
+        for part in header {
+            writer.file.write_all(part)?;
+        }
+        writer.file.flush()?;
+        Ok(writer)
+    }
+    fn write(&mut self, samples: &[f32]) -> Result<(), io::Error> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        //the sizes are u32, past 4 GiB they stay at the max and players read up to there
+        if self.data_len == u32::MAX {
+            return self.file.flush();
+        }
+        let added = u32::try_from(samples.len() * 2).unwrap_or(u32::MAX);
+        self.data_len = self.data_len.saturating_add(added);
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&self.data_len.saturating_add(36).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+//names of every output device the default host knows about
+pub fn device_names() -> Vec<String> {
+    match cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+//the `bwmp --list-devices` output
+pub fn print_devices() {
+    let default = cpal::default_host().default_output_device().and_then(|d| d.name().ok());
+    let names = device_names();
+    if names.is_empty() {
+        println!("no output devices found");
+    }
+    for name in names {
+        let marker = if Some(&name) == default.as_ref() { " (default)" } else { "" };
+        println!("{name}{marker}");
+    }
+}
+
+//opens the device with exactly this name, or the default one for None
+fn open_device(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle), String> {
+    let Some(name) = device else {
+        return OutputStream::try_default()
+            .map_err(|e| format!("couldnt open the default output device: {e}"));
+    };
+    let found = cpal::default_host()
+        .output_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == name)));
+    match found {
+        Some(d) => OutputStream::try_from_device(&d)
+            .map_err(|e| format!("couldnt open output device \"{name}\": {e}")),
+        None => {
+            let names = device_names();
+            let available = if names.is_empty() { "none".to_string() } else { names.join("\n  ") };
+            Err(format!("no output device called \"{name}\", available devices:\n  {available}"))
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use rodio::source::UniformSourceIterator;
+
+use crate::{
+    config::Config,
+    database::LibraryDb,
+    decoder::TrackDecoder,
+    eq::EqControl,
+    library,
+    loudness::LoudnessCache,
+    player::pipeline,
+    playlist::{Next, Playlist},
+    replaygain::Normalizer,
+    source::Tracked,
+    stretch::{SpeedControl, MAX_SPEED, MIN_SPEED},
+};
+
+const CHANNELS: u16 = 2;
+
+#[derive(Clone, Copy, Default)]
+pub enum PcmFormat {
+    #[default] S16Le,
+    F32Le,
+}
+
+pub fn match_pcm_format(string: &str) -> Option<PcmFormat> {
+    match string {
+        "s16le" => Some(PcmFormat::S16Le),
+        "f32le" => Some(PcmFormat::F32Le),
+        _ => None,
+    }
+}
+
+//the `bwmp --pcm-out <file>` mode, decodes the playlist in order as fast as whatever reads it
+//allows, no terminal ui, "-" writes to stdout and everything else goes to stderr
+pub fn run(config: &Config, path: &Path) {
+    let mut db = LibraryDb::open(config.rescan);
+    let tracks = match library::load_tracks(config, &mut db, &LoudnessCache::load()) {
+        Ok((t, missing)) => {
+            for location in missing {
+                eprintln!("couldnt find {location}");
+            }
+            t
+        }
+        Err(e) => fail(e),
+    };
+    let out: Box<dyn Write> = if path == Path::new("-") {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(fs::File::create(path).unwrap_or_else(|e| fail(e)))
+    };
+    let mut out = io::BufWriter::new(out);
+    let (encoding, bits) = match config.pcm_format {
+        PcmFormat::S16Le => ("signed", 16),
+        PcmFormat::F32Le => ("floating-point", 32),
+    };
+    eprintln!(
+        "writing raw pcm, play it with: sox -t raw -e {encoding} -b {bits} -c {CHANNELS} -r {} - -d",
+        config.pcm_rate
+    );
+    let normalizer = Normalizer::new(config);
+    let eq = EqControl::new(config.eq_presets.clone(), config.eq.as_deref());
+    let speed = SpeedControl::new(config.speed.clamp(MIN_SPEED, MAX_SPEED));
+    let mut playlist = Playlist::new(&tracks, config.repeat, config.shuffle);
+    let mut next = Some(Next { position: 0, index: playlist.index_at(0), from_queue: false });
+    //repeat would go round a library of broken files forever
+    let mut failed_in_a_row = 0;
+    while let Some(n) = next {
+        playlist.advance_to(&n);
+        let track = &tracks[n.index];
+        match TrackDecoder::open(track) {
+            Ok(decoder) => {
+                failed_in_a_row = 0;
+                eprintln!("playing: {}", track.long_name());
+                let gain = normalizer.factor(track, playlist.shuffle.mixes_albums());
+                let (source, _) = Tracked::new(decoder);
+                let source = pipeline(source.gain(gain), &speed, &eq);
+                let samples = UniformSourceIterator::new(source, CHANNELS, config.pcm_rate);
+                match write_samples(&mut out, samples, config.pcm_format) {
+                    Ok(()) => (),
+                    //whatever we were piped into has quit, so should we
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return,
+                    Err(e) => fail(e),
+                }
+            }
+            Err(e) => {
+                eprintln!("couldnt play {}: {e}", track.path.display());
+                failed_in_a_row += 1;
+                if failed_in_a_row >= tracks.len() {
+                    eprintln!("none of the tracks can be played");
+                    break;
+                }
+            }
+        }
+        next = playlist.peek_next(false);
+    }
+    if let Err(e) = out.flush() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            fail(e);
+        }
+    }
+}
+
+fn write_samples(out: &mut impl Write, samples: impl Iterator<Item = f32>, format: PcmFormat) -> Result<(), io::Error> {
+    for sample in samples {
+        match format {
+            PcmFormat::S16Le => {
+                out.write_all(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())?
+            }
+            PcmFormat::F32Le => out.write_all(&sample.to_le_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+fn fail(e: io::Error) -> ! {
+    eprintln!("{e}");
+    std::process::exit(1);
+}
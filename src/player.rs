@@ -1,8 +1,28 @@
-use rand::{seq::SliceRandom, thread_rng};
-use rodio::{OutputStream, OutputStreamHandle, Sink};
-use std::{collections::VecDeque, fmt, fs, io, path::PathBuf, time::Duration};
+use rodio::{Sink, Source};
+use std::{
+    fmt, io,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
 
-use crate::{sepuku, ui};
+use crate::{
+    config::{expand_home, Config},
+    database::LibraryDb,
+    decoder::TrackDecoder,
+    eq::{EqControl, Equalizer},
+    library,
+    loudness::LoudnessCache,
+    output::Backend,
+    playlist::{Next, Playlist},
+    playlist_file,
+    replaygain::Normalizer,
+    session::{self, Session},
+    source::{TrackHandle, Tracked},
+    stretch::{SpeedControl, Stretch, MAX_SPEED, MIN_SPEED},
+    track::{Track, TrackId},
+    ui,
+    watcher::Watcher,
+};
 
 #[derive(Clone, Copy, Default)]
 pub enum Repeat {
@@ -21,66 +41,163 @@ impl fmt::Display for Repeat {
     }
 }
 
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Shuffle {
+    #[default] Off,
+    Tracks,
+    //albums in a random order, the tracks of each one in order
+    Albums,
+    //like tracks but the same artist doesnt play twice in a row when it can be helped
+    Spread,
+}
+impl Shuffle {
+    //whether tracks of different albums get mixed, auto replaygain goes by this
+    pub fn mixes_albums(self) -> bool {
+        matches!(self, Shuffle::Tracks | Shuffle::Spread)
+    }
+}
+impl fmt::Display for Shuffle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Shuffle::*;
+        match self {
+            Off => write!(f, "Off"),
+            Tracks => write!(f, "Tracks"),
+            Albums => write!(f, "Albums"),
+            Spread => write!(f, "Spread"),
+        }
+    }
+}
+
+//decoder -> position, gain and fades -> time stretch -> equalizer
+pub fn pipeline(source: impl Source<Item = f32>, speed: &SpeedControl, eq: &EqControl) -> impl Source<Item = f32> {
+    Equalizer::new(Stretch::new(source, speed.clone()), eq.clone())
+}
+
+//how long before the end of a track the next one gets appended to the sink
+const PRELOAD: Duration = Duration::from_secs(5);
+
+//how often the session gets saved while playing, so a crash doesnt lose much
+const SAVE_SESSION: Duration = Duration::from_secs(30);
+
+//a track that is already sitting in the sink behind the current one
+struct UpNext {
+    next: Next,
+    handle: TrackHandle,
+}
+
 pub struct Player {
-    _stream: OutputStream,
-    output_stream_handle: OutputStreamHandle,
-    music_list: Vec<PathBuf>,
-    shuffled_list: Vec<u16>,
-    pub queue: VecDeque<u16>,
+    output: Box<dyn Backend>,
+    music_list: Vec<Track>,
+    pub playlist: Playlist,
     now_playing: Sink,
-    now_playing_id: u16,
-    repeat: Repeat,
-    shuffle: bool,
+    //the sink of the previous track while it fades out
+    fading: Option<Sink>,
+    playing: TrackHandle,
+    up_next: Option<UpNext>,
+    //the track in up_next sends on this when the sink gets to it
+    next_started: Receiver<()>,
+    on_next_start: Sender<()>,
     pub ui: ui::Ui,
     duration: Duration,
-    time_playing: Duration,
+    crossfade: Duration,
+    skip_fade: Duration,
+    normalizer: Normalizer,
+    eq: EqControl,
+    speed: SpeedControl,
+    watcher: Option<Watcher>,
+    //kept around so the watcher doesnt read and write them again for every change
+    db: LibraryDb,
+    loudness: LoudnessCache,
+    //the last changes left no tracks, the list on screen stays until the next ones bring some
+    library_emptied: bool,
+    //what was searched for last, lowercase, W saves what matches it
+    last_search: String,
+    //the dirs or playlist being played, the session is saved under it
+    library: String,
+    session_saved: Instant,
 }
 
 impl Player {
-    pub fn new(path: &str, repeat: Repeat, shuffle: bool, vol: f32) -> Self {
-        let (stream, stream_handle) =
-            OutputStream::try_default().expect("couldnt find default output device");
+    //the output is opened before the terminal goes raw, so its errors can be printed normally
+    pub fn new(config: &Config, output: Box<dyn Backend>) -> Self {
         let (sink, _) = Sink::new_idle();
-        let mlist = match create_music_list(path) {
+        let mut db = LibraryDb::open(config.rescan);
+        let loudness = LoudnessCache::load();
+        let (mlist, missing) = match library::load_tracks(config, &mut db, &loudness) {
             Ok(m) => m,
-            Err(e) => {sepuku(); panic!("{e}")}
+            Err(e) => panic!("{e}"),
         };
-        let shuffled = shuffle_list(mlist.len() as u16);
-        sink.set_volume(vol);
-        Self {
-            _stream: stream,                     //must not be freed
-            output_stream_handle: stream_handle, //also must not be freed
-            ui: ui::Ui::new(mlist.clone()),
+        sink.set_volume(config.volume);
+        let (on_next_start, next_started) = mpsc::channel();
+        let mut selfish = Self {
+            output,
+            ui: ui::Ui::new(&mlist),
+            playlist: Playlist::new(&mlist, config.repeat, config.shuffle),
             music_list: mlist,
-            shuffled_list: shuffled,
-            queue: VecDeque::new(),
             now_playing: sink,
-            now_playing_id: 0,
-            repeat: repeat,
-            shuffle: shuffle,
+            fading: None,
+            playing: TrackHandle::default(),
+            up_next: None,
+            next_started,
+            on_next_start,
             duration: Duration::new(0, 0),
-            time_playing: Duration::new(0, 0),
+            crossfade: config.crossfade,
+            skip_fade: config.skip_fade,
+            normalizer: Normalizer::new(config),
+            eq: EqControl::new(config.eq_presets.clone(), config.eq.as_deref()),
+            speed: SpeedControl::new(config.speed.clamp(MIN_SPEED, MAX_SPEED)),
+            //a playlist doesnt change when files get dropped into its dirs
+            watcher: match config.playlist {
+                Some(_) => None,
+                None => Watcher::new(&config.dirs, config.depth),
+            },
+            db,
+            loudness,
+            library_emptied: false,
+            last_search: String::new(),
+            library: session::library_key(config),
+            session_saved: Instant::now(),
+        };
+        selfish.ui.update_eq(&selfish.eq);
+        selfish.ui.update_shuffle(config.shuffle);
+        selfish.ui.update_repeat(config.repeat);
+        selfish.ui.update_volume(config.volume);
+        if !missing.is_empty() {
+            let names: Vec<&str> = missing.iter().map(|m| m.rsplit('/').next().unwrap_or(m)).collect();
+            let what = if config.playlist.is_some() { "playlist entries" } else { "music dirs" };
+            let message = format!("couldnt find {} {what}: {}", missing.len(), names.join(", "));
+            selfish.ui.show_message(&message);
         }
+        selfish
     }
-    //toggles shuffle
+    //cycles throu the shuffle modes, whatever plays now comes first in the new order
     pub fn toggle_shuffle(&mut self) {
-        self.shuffle = !self.shuffle;
-        self.ui.update_shuffle(self.shuffle);
+        use Shuffle::*;
+        let shuffle = match self.playlist.shuffle {
+            Off => Tracks,
+            Tracks => Albums,
+            Albums => Spread,
+            Spread => Off,
+        };
+        self.playlist.set_shuffle(shuffle);
+        self.forget_next();
+        self.ui.update_shuffle(shuffle);
     }
     //cycles throu repeat options, alternatively you can set a specific one
     pub fn toggle_repeat(&mut self, o: Option<Repeat>) {
-        if o.is_some() {
-            self.repeat = o.unwrap();
-            self.ui.update_repeat(self.repeat);
+        self.forget_next();
+        if let Some(r) = o {
+            self.playlist.repeat = r;
+            self.ui.update_repeat(self.playlist.repeat);
             return;
         }
         use Repeat::*;
-        self.repeat = match self.repeat {
+        self.playlist.repeat = match self.playlist.repeat {
             Repeat::None => Single,
             Single => All,
             All => Repeat::None,
         };
-        self.ui.update_repeat(self.repeat);
+        self.ui.update_repeat(self.playlist.repeat);
     }
     //returns current volume
     pub fn volume(&self) -> f32 {
@@ -88,133 +205,477 @@ impl Player {
     }
     //changes volume to a specific float value
     pub fn change_volume(&mut self, volume: f32) {
-        if volume >= -0.01 && volume <= 2.01 {
+        if (-0.01..=2.01).contains(&volume) {
             self.now_playing.set_volume(volume);
+            if let Some(fading) = &self.fading {
+                fading.set_volume(volume);
+            }
             self.ui.update_volume(volume);
         }
     }
     //toggled pause
     pub fn toggle_pause(&mut self) {
+        let sinks = std::iter::once(&self.now_playing).chain(self.fading.as_ref());
         if self.now_playing.is_paused() {
-            self.now_playing.play();
+            sinks.for_each(|s| s.play());
         } else {
-            self.now_playing.pause();
+            sinks.for_each(|s| s.pause());
         }
         self.ui.update_status(self.now_playing.is_paused());
     }
+    //turns the equalizer on or off, takes effect on the next frame of the playing track
+    pub fn toggle_eq(&mut self) {
+        self.eq.toggle();
+        self.ui.update_eq(&self.eq);
+    }
+    //switches to the next preset, on or off
+    pub fn next_eq_preset(&mut self) {
+        self.eq.next_preset();
+        self.ui.update_eq(&self.eq);
+    }
+    pub fn speed(&self) -> f32 {
+        self.speed.get()
+    }
+    //changes the playback speed, pitch stays the same
+    pub fn change_speed(&mut self, speed: f32) {
+        //rounded so stepping by 0.1 lands exactly on 1 again
+        let speed = ((speed * 10.0).round() / 10.0).clamp(MIN_SPEED, MAX_SPEED);
+        self.speed.set(speed);
+        self.update_bar();
+    }
+    //durations are in track time, this turns wall clock time into it
+    fn track_time(&self, wall: Duration) -> Duration {
+        wall.mul_f32(self.speed.get())
+    }
     //check is the player is playing or paused
     pub fn is_playing(&self) -> bool {
         !self.now_playing.is_paused()
     }
-    //check if sink has finished playing
-    pub fn is_finished(&self) -> bool {
-        self.now_playing.empty()
-    }
     pub fn put_selected_in_queue(&mut self) {
-        self.queue.push_back(self.ui.get_selected());
-        self.ui.update_queue(&self.queue);
-    }
-    //play a file with a specific id within a list
-    pub fn play(&mut self, id: u16) {
-        let file = fs::File::open(self.music_list[id as usize].clone()).unwrap();
-        let name = self.music_list[id as usize]
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap();
-        self.duration = mp3_duration::from_file(&file).unwrap();
-        self.time_playing = Duration::new(0, 0);
-        self.ui.change_name(name);
-        self.ui.highlight_playing(id);
-        self.now_playing = self.output_stream_handle.play_once(file).unwrap();
-        self.now_playing.set_volume(self.volume());
-    }
-    //plays previous file in list, does nothing when the first file is playing
-    pub fn play_previous(&mut self) {
-        if self.now_playing_id == 0 {
-            return;
+        let Some(index) = self.ui.get_selected().checked_sub(1) else { return };
+        self.playlist.queue.push_back(self.music_list[index].id);
+        self.forget_next();
+        self.show_queue();
+    }
+    //puts the selected track at the front of the queue, so it plays after the current one
+    pub fn play_selected_next(&mut self) {
+        let Some(index) = self.ui.get_selected().checked_sub(1) else { return };
+        self.playlist.queue.push_front(self.music_list[index].id);
+        self.forget_next();
+        self.show_queue();
+    }
+    pub fn select_in_panel(&mut self, offset: isize) {
+        let position = self.ui.get_panel_selected().saturating_add_signed(offset);
+        self.ui.select_in_panel(position);
+    }
+    pub fn remove_queued(&mut self) {
+        if self.playlist.queue.remove(self.ui.get_panel_selected()).is_some() {
+            self.forget_next();
+            self.show_queue();
         }
-        self.time_playing = Duration::new(0, 0);
-        self.now_playing_id -= 1;
-        let mut id = self.now_playing_id;
-        if self.shuffle {
-            id = self.shuffled_list[id as usize]
+    }
+    //moves the selected queue entry up (-1) or down (1), the selection goes with it
+    pub fn move_queued(&mut self, offset: isize) {
+        let from = self.ui.get_panel_selected();
+        let Some(to) = from.checked_add_signed(offset).filter(|to| *to < self.playlist.queue.len()) else { return };
+        self.playlist.queue.swap(from, to);
+        self.forget_next();
+        self.show_queue();
+        self.ui.select_in_panel(to);
+    }
+    pub fn clear_queue(&mut self) {
+        self.playlist.queue.clear();
+        self.forget_next();
+        self.show_queue();
+    }
+    //plays the selected queue entry right away and takes it out of the queue
+    pub fn jump_to_queued(&mut self) {
+        let Some(id) = self.playlist.queue.remove(self.ui.get_panel_selected()) else { return };
+        self.show_queue();
+        let Some(index) = self.playlist.index_of(id) else { return };
+        self.playlist.position = self.playlist.position_of(index);
+        self.play(index);
+    }
+    //plays the selected history entry again, like picking it from the list
+    pub fn play_from_history(&mut self) {
+        let history = &self.playlist.history;
+        let Some(id) = history.len().checked_sub(self.ui.get_panel_selected() + 1).map(|i| history[i]) else { return };
+        let Some(index) = self.playlist.index_of(id) else { return };
+        self.playlist.position = self.playlist.position_of(index);
+        self.play(index);
+    }
+    fn show_history(&mut self) {
+        let numbers: Vec<usize> = self
+            .playlist
+            .history
+            .iter()
+            .rev()
+            .filter_map(|id| self.playlist.index_of(*id))
+            .map(|i| i + 1)
+            .collect();
+        self.ui.update_history(&numbers);
+    }
+    //puts the queue back where prompts were typed
+    pub fn show_queue(&mut self) {
+        let numbers: Vec<usize> = self
+            .playlist
+            .queue
+            .iter()
+            .filter_map(|id| self.playlist.index_of(*id))
+            .map(|i| i + 1)
+            .collect();
+        self.ui.update_queue(&numbers);
+    }
+    //plays the track at an index of music_list, what was playing gets the short skip fade
+    pub fn play(&mut self, index: usize) {
+        let fade = if self.playing.has_started() && !self.playing.is_finished() {
+            self.track_time(self.skip_fade)
+        } else {
+            Duration::ZERO
+        };
+        if !self.start(index, fade) {
+            self.skip_broken(fade);
+        }
+    }
+    //returns false if the track couldnt be opened, nothing changes then
+    fn start(&mut self, index: usize, fade: Duration) -> bool {
+        let Some(decoder) = self.open(index) else { return false };
+        let track = &self.music_list[index];
+        self.duration = track.duration.or(decoder.total_duration()).unwrap_or_default();
+        self.ui.change_name(&track.long_name());
+        self.ui.highlight_playing(index);
+        let volume = self.volume();
+        //whatever was queued behind the old track must not play after it fades out
+        if let Some(up_next) = self.up_next.take() {
+            up_next.handle.cancel();
+        }
+        self.playlist.played(track.id);
+        let (sink, sink_output) = Sink::new_idle();
+        self.output.play(sink_output);
+        let old = std::mem::replace(&mut self.now_playing, sink);
+        //dropping a sink stops it, so without a fade the old track is cut right here
+        self.fading = None;
+        if !fade.is_zero() {
+            self.playing.fade_out(fade);
+            self.fading = Some(old);
+        }
+        let gain = self.normalizer.factor(track, self.playlist.shuffle.mixes_albums());
+        let (source, handle) = Tracked::new(decoder);
+        self.playing = handle;
+        self.now_playing.append(pipeline(source.gain(gain).fade_in(fade), &self.speed, &self.eq));
+        self.now_playing.set_volume(volume);
+        self.show_history();
+        true
+    }
+    //opens a track for playing, one that fails gets marked as broken and never tried again
+    fn open(&mut self, index: usize) -> Option<TrackDecoder> {
+        let track = &mut self.music_list[index];
+        if track.broken {
+            return None;
+        }
+        match TrackDecoder::open(track) {
+            Ok(decoder) => Some(decoder),
+            Err(e) => {
+                track.broken = true;
+                let message = format!("couldnt play {}: {e}", track.display_name());
+                self.ui.mark_broken(index);
+                self.ui.show_message(&message);
+                None
+            }
+        }
+    }
+    //moves on past tracks that wont open, every track gets one try so a library that is
+    //all broken stops instead of spinning
+    fn skip_broken(&mut self, fade: Duration) {
+        for _ in 0..self.music_list.len() {
+            let Some(next) = self.playlist.peek_next(true) else { break };
+            self.advance_to(&next);
+            if self.start(next.index, fade) {
+                return;
+            }
+        }
+        if self.music_list.iter().all(|t| t.broken) {
+            self.ui.show_message("none of the tracks can be played");
+        }
+        //a fresh handle has neither started nor finished, so update() leaves it alone
+        self.playing = TrackHandle::default();
+        self.duration = Duration::ZERO;
+        self.now_playing.stop();
+        self.fading = None;
+    }
+    //goes back through what actually played, then through the list
+    pub fn play_previous(&mut self) {
+        if let Some(index) = self.playlist.previous() {
+            self.play(index);
         }
-
-        self.play(id);
     }
     //plays next file in list, repeat value alters the behavior
     pub fn play_next(&mut self, skip: bool) {
-        if !self.queue.is_empty() {
-            self.now_playing_id = (self.queue.pop_front().unwrap() - 1).into();
-            self.ui.update_queue(&self.queue);
-            self.play(self.now_playing_id);
+        let Some(next) = self.playlist.peek_next(skip) else { return };
+        self.advance_to(&next);
+        self.play(next.index);
+    }
+    fn advance_to(&mut self, next: &Next) {
+        self.playlist.advance_to(next);
+        if next.from_queue {
+            self.show_queue();
+        }
+    }
+    //sleeps until the next tick, or less when the next track starts so the ui switches over with it
+    pub fn wait(&self, tick: Duration) {
+        let _ = self.next_started.recv_timeout(tick);
+    }
+    //called every tick, keeps the next track appended to the sink and switches the ui over
+    //once the sink has moved on to it
+    pub fn update(&mut self) {
+        //one that ends before its first sample is switched to as well, so it gets skipped below
+        let started = self.up_next.take_if(|u| u.handle.has_started() || u.handle.is_finished());
+        if let Some(up_next) = started {
+            self.switch_to(up_next);
+        } else if self.up_next.is_none() && self.playing.is_finished() {
+            //nothing was queued up in time, fall back to starting it from scratch
+            self.play_next(false);
+        }
+        self.start_crossfade();
+        self.prepare_next();
+        if self.fading.as_ref().is_some_and(|s| s.empty()) {
+            self.fading = None;
+        }
+    }
+    //crossfading a track into itself on repeat sounds wrong, those go through the gapless path
+    fn will_crossfade(&self, next: &Next) -> bool {
+        !self.crossfade.is_zero()
+            && !self.duration.is_zero()
+            && (next.from_queue || !matches!(self.playlist.repeat, Repeat::Single))
+    }
+    fn start_crossfade(&mut self) {
+        if self.playing.is_finished() {
             return;
         }
-        match self.repeat {
-            Repeat::None => {
-                if self.now_playing_id + 1 >= self.music_list.len() as u16 {
-                    return;
-                }
-                self.now_playing_id += 1
-            }
-            Repeat::Single => {
-                if skip {
-                    self.now_playing_id += 1
-                }
-            }
-            Repeat::All => {
-                if self.now_playing_id + 1 >= self.music_list.len() as u16 {
-                    self.now_playing_id = 0;
-                } else {
-                    self.now_playing_id += 1;
-                }
-            }
+        let remaining = self.duration.saturating_sub(self.playing.position());
+        //short tracks would otherwise start fading out as soon as they start
+        if remaining > self.track_time(self.crossfade).min(self.duration / 2) {
+            return;
         }
-
-        self.time_playing = Duration::new(0, 0);
-        let id = if self.shuffle {
-            self.shuffled_list[self.now_playing_id as usize]
+        let Some(next) = self.playlist.peek_next(false) else { return };
+        if !self.will_crossfade(&next) {
+            return;
+        }
+        self.advance_to(&next);
+        if !self.start(next.index, remaining) {
+            self.skip_broken(remaining);
+        }
+    }
+    fn prepare_next(&mut self) {
+        //nothing is playing after skip_broken gave up
+        if self.up_next.is_some() || self.playing.is_finished() || !self.playing.has_started() {
+            return;
+        }
+        //an unknown duration is 0, those get their successor right away
+        let remaining = self.duration.saturating_sub(self.playing.position());
+        if !self.duration.is_zero() && remaining > self.track_time(PRELOAD) {
+            return;
+        }
+        let Some(next) = self.playlist.peek_next(false) else { return };
+        if self.will_crossfade(&next) {
+            return;
+        }
+        //a broken one is left for update() to skip once the current track ends
+        let Some(decoder) = self.open(next.index) else { return };
+        let gain = self.normalizer.factor(&self.music_list[next.index], self.playlist.shuffle.mixes_albums());
+        let (source, handle) = Tracked::new(decoder);
+        let source = source.gain(gain).notify_start(self.on_next_start.clone());
+        self.now_playing.append(pipeline(source, &self.speed, &self.eq));
+        self.up_next = Some(UpNext { next, handle });
+    }
+    fn switch_to(&mut self, up_next: UpNext) {
+        self.advance_to(&up_next.next);
+        let track = &self.music_list[up_next.next.index];
+        self.duration = track.duration.unwrap_or_default();
+        self.ui.change_name(&track.long_name());
+        self.ui.highlight_playing(up_next.next.index);
+        self.playlist.played(track.id);
+        self.playing = up_next.handle;
+        self.show_history();
+    }
+    //called when something changes what should play next, the sink skips the cancelled track
+    fn forget_next(&mut self) {
+        let Some(up_next) = &self.up_next else { return };
+        //too late, the sink is already playing it and update() will catch up
+        if up_next.handle.has_started() {
+            return;
+        }
+        up_next.handle.cancel();
+        self.up_next = None;
+    }
+    pub fn play_selected(&mut self) {
+        let Some(index) = self.ui.get_selected().checked_sub(1) else { return };
+        self.playlist.position = self.playlist.position_of(index);
+        self.play(index);
+    }
+    //jumps to a position in the current track
+    pub fn seek(&mut self, to: Duration) {
+        //an unknown duration is 0, dont clamp to that
+        let to = if self.duration.is_zero() { to } else { to.min(self.duration) };
+        self.playing.seek(to);
+        self.ui.update_bar(self.duration, to, self.speed.get());
+    }
+    //jumps forward or back by a number of seconds
+    pub fn seek_relative(&mut self, secs: i64) {
+        let offset = Duration::from_secs(secs.unsigned_abs());
+        let position = self.playing.position();
+        let to = if secs < 0 {
+            position.saturating_sub(offset)
         } else {
-            self.now_playing_id
+            position.saturating_add(offset)
         };
-        self.play(id);
+        self.seek(to);
     }
-    pub fn play_selected(&mut self) {
-        self.now_playing_id = self.ui.get_selected() - 1;
-        self.play((self.ui.get_selected() - 1).into());
+    //jumps to a percentage of the track, used by the number keys
+    pub fn seek_percent(&mut self, percent: u32) {
+        self.seek(self.duration * percent / 100);
+    }
+    //updates the time bar with what the audio output has actually played
+    pub fn update_bar(&mut self) {
+        self.ui.update_bar(self.duration, self.playing.position(), self.speed.get());
+    }
+    //picks up files that were added, removed or renamed since the last tick, what is playing,
+    //the queue and the selection all stay on the tracks they were on
+    pub fn check_library(&mut self) {
+        let Some(watcher) = &self.watcher else { return };
+        let changes = watcher.changes();
+        if changes.is_empty() {
+            return;
+        }
+        //indexes are about to change, a track waiting in the sink would keep its old one
+        self.update();
+        self.forget_next();
+        let current = self.music_list.get(self.ui.now_playing).map(|t| t.id);
+        let selected = self.ui.get_selected().checked_sub(1).map(|i| self.music_list[i].id);
+        let mut tracks = if self.library_emptied { Vec::new() } else { self.music_list.clone() };
+        let renamed = library::apply_changes(&mut tracks, changes, &mut self.db, &self.loudness);
+        //an empty list has nothing to show or play, the changes are kept and the next ones
+        //start from nothing
+        self.library_emptied = tracks.is_empty();
+        if self.library_emptied {
+            return;
+        }
+        self.playlist.update(&tracks, &renamed, current);
+        self.music_list = tracks;
+        let follow = |id| renamed.get(&id).copied().unwrap_or(id);
+        let selected = selected.and_then(|id| self.playlist.index_of(follow(id)));
+        let playing = current.and_then(|id| self.playlist.index_of(follow(id)));
+        self.ui.set_tracks(&self.music_list, selected, playing);
+        self.show_queue();
+        self.show_history();
     }
-    //updates the time bar
-    pub fn update_bar(&mut self, t: Duration) {
-        self.ui.update_bar(self.duration, self.time_playing);
-        self.time_playing = self.time_playing.saturating_add(t);
+    //writes the queue, or every track matching the last search, to an m3u8 file
+    pub fn save_playlist(&mut self, file: &str, matching: bool) {
+        if file.trim().is_empty() {
+            return;
+        }
+        let mut path = expand_home(file.trim());
+        if path.extension().is_none() {
+            path.set_extension("m3u8");
+        }
+        let tracks: Vec<&Track> = if matching {
+            self.music_list.iter().filter(|t| t.matches(&self.last_search)).collect()
+        } else {
+            self.playlist
+                .queue
+                .iter()
+                .filter_map(|id| self.playlist.index_of(*id))
+                .map(|i| &self.music_list[i])
+                .collect()
+        };
+        //m3u has no way to point at part of a file, a cue song would come back as the whole album
+        let (songs, tracks): (Vec<&Track>, Vec<&Track>) = tracks.into_iter().partition(|t| t.cue.is_some());
+        let left_out = match songs.len() {
+            0 => String::new(),
+            n => format!(", left out {n} cue sheet songs"),
+        };
+        let message = if tracks.is_empty() && !songs.is_empty() {
+            "cue sheet songs cant be saved to a playlist".to_string()
+        } else {
+            match playlist_file::save(&path, &tracks) {
+                Ok(()) => format!("saved {} tracks to {}{left_out}", tracks.len(), path.display()),
+                Err(e) => format!("couldnt save {}: {e}", path.display()),
+            }
+        };
+        self.ui.show_message(&message);
+    }
+    //carries on where the last session with this library left off, false when there is none,
+    //volume, repeat and shuffle given as arguments win over the saved ones
+    pub fn resume(&mut self, config: &Config) -> bool {
+        let Some(session) = Session::load(&self.library) else { return false };
+        let Some(index) = session.track.and_then(|id| self.playlist.index_of(id)) else { return false };
+        if !config.volume_given {
+            self.change_volume(session.volume);
+        }
+        if !config.repeat_given {
+            self.toggle_repeat(Some(session.repeat));
+        }
+        if !config.shuffle_given {
+            self.playlist.shuffle = session.shuffle;
+            self.ui.update_shuffle(session.shuffle);
+        }
+        //the saved order only fits the shuffle it was made with, otherwise a new one starts here
+        let order = if self.playlist.shuffle == session.shuffle { &session.order[..] } else { &[] };
+        self.playlist.restore(order, index);
+        let known = |ids: Vec<TrackId>| ids.into_iter().filter(|id| self.playlist.index_of(*id).is_some()).collect();
+        let (queue, history) = (known(session.queue), known(session.history));
+        self.playlist.queue = queue;
+        self.playlist.history = history;
+        if let Some(selected) = session.selected.and_then(|id| self.playlist.index_of(id)) {
+            self.ui.highlight_searched(Some(selected));
+        }
+        self.play(index);
+        //a track that wont open anymore was skipped, the next one starts from the beginning
+        if self.ui.now_playing == index {
+            self.seek(session.position);
+        }
+        self.show_queue();
+        self.show_history();
+        true
+    }
+    pub fn save_session(&self) -> Result<(), io::Error> {
+        let track = self.music_list.get(self.ui.now_playing);
+        //a track that played to the end starts again from the beginning
+        let position = match self.playing.is_finished() {
+            true => Duration::ZERO,
+            false => self.playing.position(),
+        };
+        let id = |i: usize| self.music_list[i].id;
+        Session {
+            library: self.library.clone(),
+            track: track.map(|t| t.id),
+            position,
+            volume: self.volume(),
+            repeat: self.playlist.repeat,
+            shuffle: self.playlist.shuffle,
+            order: self.playlist.order(),
+            queue: self.playlist.queue.iter().copied().collect(),
+            history: self.playlist.history.iter().copied().collect(),
+            selected: self.ui.get_selected().checked_sub(1).map(id),
+        }
+        .save()
+    }
+    //called every tick, saves the session every SAVE_SESSION
+    pub fn autosave_session(&mut self) {
+        if self.session_saved.elapsed() < SAVE_SESSION {
+            return;
+        }
+        self.session_saved = Instant::now();
+        //not being able to save only means the next start doesnt resume
+        let _ = self.save_session();
     }
     pub fn search_for(&mut self, search_str: &str) {
         self.ui.display_searching(search_str);
+        self.last_search = search_str.to_lowercase();
         if search_str.len() > 2 {
-            self.ui.highlight_searched(search_str);
+            let search_str = search_str.to_lowercase();
+            let found = self.music_list.iter().position(|t| t.matches(&search_str));
+            self.ui.highlight_searched(found);
         }
     }
 }
-
-//creates a music list from a given path
-fn create_music_list(path: &str) -> Result<Vec<PathBuf>, io::Error> {
-    let mut paths: Vec<PathBuf> = fs::read_dir(path)?
-        .map(|res| res.map(|p| p.path()))
-        .collect::<Result<Vec<_>, io::Error>>()?;
-    paths.retain(|m| match m.extension() {
-        Some(p) => p.to_os_string().eq("mp3"),
-        None => false,
-    });
-    if paths.len() == 0 {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "no music files found!"));
-    }
-    Ok(paths)
-}
-
-//returns a vec of random numbers, used to play random music
-fn shuffle_list(len: u16) -> Vec<u16> {
-    let mut shuffled: Vec<u16> = (0..len).collect();
-    shuffled.shuffle(&mut thread_rng());
-    shuffled
-}
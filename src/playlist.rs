@@ -0,0 +1,356 @@
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+use crate::{
+    player::{Repeat, Shuffle},
+    track::{Track, TrackId},
+};
+
+//how many played tracks p can go back through
+const HISTORY_LEN: usize = 100;
+
+//what plays next, position is the new playlist position and index is the one in music_list
+pub struct Next {
+    pub position: usize,
+    pub index: usize,
+    pub from_queue: bool,
+}
+
+//the order things play in, without anything to play them with
+pub struct Playlist {
+    //music_list as ids, to find tracks again after it changed
+    ids: Vec<TrackId>,
+    //where every track sits in music_list
+    indexes: HashMap<TrackId, usize>,
+    shuffled_list: Vec<usize>,
+    //the order after this one, made ahead so the track that starts it can be preloaded
+    next_cycle: Vec<usize>,
+    //the tracks of every album in the order they play in, for album shuffle
+    albums: Vec<Vec<usize>>,
+    //a number for every track that is the same for tracks by the same artist, for spread shuffle
+    artists: Vec<usize>,
+    pub queue: VecDeque<TrackId>,
+    //what played before the current track, newest last
+    pub history: VecDeque<TrackId>,
+    current: Option<TrackId>,
+    //where in the list (or the shuffled list) playback is
+    pub position: usize,
+    pub repeat: Repeat,
+    pub shuffle: Shuffle,
+}
+
+impl Playlist {
+    pub fn new(tracks: &[Track], repeat: Repeat, shuffle: Shuffle) -> Self {
+        let mut selfish = Self {
+            ids: tracks.iter().map(|t| t.id).collect(),
+            indexes: tracks.iter().enumerate().map(|(i, t)| (t.id, i)).collect(),
+            shuffled_list: Vec::new(),
+            next_cycle: Vec::new(),
+            albums: albums(tracks),
+            artists: artists(tracks),
+            queue: VecDeque::new(),
+            history: VecDeque::new(),
+            current: None,
+            position: 0,
+            repeat,
+            shuffle,
+        };
+        selfish.reshuffle(None);
+        selfish
+    }
+    //None once the track is gone from the library
+    pub fn index_of(&self, id: TrackId) -> Option<usize> {
+        self.indexes.get(&id).copied()
+    }
+    //the music_list index of a position, goes throu the shuffled list when shuffling
+    pub fn index_at(&self, position: usize) -> usize {
+        if self.shuffle != Shuffle::Off {
+            self.shuffled_list[position]
+        } else {
+            position
+        }
+    }
+    //the position of a music_list index, the same thing unless shuffling
+    pub fn position_of(&self, index: usize) -> usize {
+        if self.shuffle != Shuffle::Off {
+            self.shuffled_list.iter().position(|i| *i == index).unwrap_or(0)
+        } else {
+            index
+        }
+    }
+    //works out what comes after the current track without changing anything
+    pub fn peek_next(&self, skip: bool) -> Option<Next> {
+        if let Some(index) = self.queue.front().and_then(|id| self.index_of(*id)) {
+            return Some(Next { position: self.position_of(index), index, from_queue: true });
+        }
+        let last = self.ids.len() - 1;
+        let position = match self.repeat {
+            Repeat::None => {
+                if self.position >= last {
+                    return None;
+                }
+                self.position + 1
+            }
+            Repeat::Single if !skip => self.position,
+            Repeat::Single | Repeat::All => {
+                if self.position >= last {
+                    0
+                } else {
+                    self.position + 1
+                }
+            }
+        };
+        //going round starts a new shuffled order
+        if self.wraps_to(position) && self.shuffle != Shuffle::Off {
+            return Some(Next { position, index: self.next_cycle[0], from_queue: false });
+        }
+        Some(Next { position, index: self.index_at(position), from_queue: false })
+    }
+    fn wraps_to(&self, position: usize) -> bool {
+        position == 0 && self.position + 1 >= self.ids.len() && self.ids.len() > 1
+    }
+    //follows music_list after files were added, removed or renamed, the current track keeps its
+    //place and tracks that are new get shuffled in after it
+    pub fn update(&mut self, tracks: &[Track], renamed: &HashMap<TrackId, TrackId>, current: Option<TrackId>) {
+        let follow = |id: &TrackId| renamed.get(id).copied().unwrap_or(*id);
+        let old_sorted: Vec<TrackId> = self.ids.iter().map(follow).collect();
+        let old_shuffled: Vec<TrackId> = self.shuffled_list.iter().map(|&i| old_sorted[i]).collect();
+        let old_order = if self.shuffle != Shuffle::Off { &old_shuffled } else { &old_sorted };
+        let current = current.map(|id| follow(&id));
+        let at = current
+            .and_then(|c| old_order.iter().position(|id| *id == c))
+            .unwrap_or(self.position)
+            .min(old_order.len() - 1);
+
+        self.ids = tracks.iter().map(|t| t.id).collect();
+        self.indexes = self.ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        self.albums = albums(tracks);
+        self.artists = artists(tracks);
+        let queue = self.queue.iter().map(follow).filter(|id| self.indexes.contains_key(id)).collect();
+        self.queue = queue;
+        let history = self.history.iter().map(follow).filter(|id| self.indexes.contains_key(id)).collect();
+        self.history = history;
+        self.current = self.current.map(|id| follow(&id));
+        //the current track, or the closest one before it when its file is gone
+        let anchor = old_order[..=at].iter().rev().find_map(|id| self.index_of(*id));
+        let mut shuffled: Vec<usize> = old_shuffled.iter().filter_map(|id| self.index_of(*id)).collect();
+        let after = anchor.and_then(|a| shuffled.iter().position(|i| *i == a)).map_or(0, |p| p + 1);
+        let mut known = vec![false; self.ids.len()];
+        shuffled.iter().for_each(|i| known[*i] = true);
+        let added = known.iter().any(|k| !k);
+        match self.shuffle {
+            //new tracks cant go just anywhere in album or spread order, it is worked out again instead
+            Shuffle::Albums | Shuffle::Spread if added => self.reshuffle(anchor),
+            _ => {
+                for (index, _) in known.iter().enumerate().filter(|(_, k)| !**k) {
+                    shuffled.insert(thread_rng().gen_range(after..=shuffled.len()), index);
+                }
+                self.shuffled_list = shuffled;
+                self.next_cycle = self.shuffled(None, self.shuffled_list.last().copied());
+            }
+        }
+        self.position = match anchor {
+            Some(a) if self.shuffle != Shuffle::Off => self.shuffled_list.iter().position(|i| *i == a).unwrap_or(0),
+            Some(a) => a,
+            None => 0,
+        };
+    }
+    pub fn advance_to(&mut self, next: &Next) {
+        if !next.from_queue && self.wraps_to(next.position) && self.shuffle != Shuffle::Off {
+            self.shuffled_list = std::mem::take(&mut self.next_cycle);
+            self.next_cycle = self.shuffled(None, self.shuffled_list.last().copied());
+        }
+        self.position = next.position;
+        if next.from_queue {
+            self.queue.pop_front();
+        }
+    }
+    //the shuffled order as ids, for the session file
+    pub fn order(&self) -> Vec<TrackId> {
+        self.shuffled_list.iter().map(|i| self.ids[*i]).collect()
+    }
+    //puts back the shuffled order of the last session with current as the current track, the
+    //order is only kept when it still has every track of the library
+    pub fn restore(&mut self, order: &[TrackId], current: usize) {
+        let shuffled: Vec<usize> = order.iter().filter_map(|id| self.index_of(*id)).collect();
+        let mut seen = vec![false; self.ids.len()];
+        shuffled.iter().for_each(|i| seen[*i] = true);
+        if shuffled.len() == self.ids.len() && seen.iter().all(|s| *s) {
+            self.shuffled_list = shuffled;
+            self.next_cycle = self.shuffled(None, self.shuffled_list.last().copied());
+        } else if self.shuffle != Shuffle::Off {
+            self.reshuffle(Some(current));
+        }
+        self.position = self.position_of(current);
+    }
+    //the current track stays the current one and everything else gets shuffled after it
+    pub fn set_shuffle(&mut self, shuffle: Shuffle) {
+        let current = self.current.and_then(|id| self.index_of(id));
+        self.shuffle = shuffle;
+        if shuffle != Shuffle::Off {
+            self.reshuffle(current);
+        }
+        self.position = current.map_or(0, |i| self.position_of(i));
+    }
+    //a new order starting with first, and the one that follows it
+    fn reshuffle(&mut self, first: Option<usize>) {
+        self.shuffled_list = self.shuffled(first, None);
+        self.next_cycle = self.shuffled(None, self.shuffled_list.last().copied());
+    }
+    //an order for the shuffle mode, first (or its album) goes at the front, otherwise it doesnt
+    //start with the same track, album or artist as previous, which played right before
+    fn shuffled(&self, first: Option<usize>, previous: Option<usize>) -> Vec<usize> {
+        let mut rng = thread_rng();
+        match self.shuffle {
+            Shuffle::Off | Shuffle::Tracks => {
+                let mut shuffled = shuffle_list(self.ids.len());
+                let at = match first {
+                    Some(first) => shuffled.iter().position(|i| *i == first),
+                    None if shuffled.len() > 1 && shuffled.first() == previous.as_ref() => {
+                        Some(rng.gen_range(1..shuffled.len()))
+                    }
+                    None => None,
+                };
+                if let Some(at) = at {
+                    shuffled.swap(0, at);
+                }
+                shuffled
+            }
+            Shuffle::Albums => {
+                let mut albums: Vec<&Vec<usize>> = self.albums.iter().collect();
+                albums.shuffle(&mut rng);
+                let at = match (first, previous) {
+                    (Some(first), _) => albums.iter().position(|a| a.contains(&first)),
+                    (None, Some(previous)) if albums.len() > 1 && albums[0].contains(&previous) => {
+                        Some(rng.gen_range(1..albums.len()))
+                    }
+                    _ => None,
+                };
+                if let Some(at) = at {
+                    albums.swap(0, at);
+                }
+                albums.into_iter().flatten().copied().collect()
+            }
+            Shuffle::Spread => self.spread(first, previous),
+        }
+    }
+    //every artist gets shuffled on its own, then they are dealt from at random, weighted by how
+    //many tracks each has left so none gets used up early, and never the one that just played
+    //unless nothing else is left
+    fn spread(&self, first: Option<usize>, previous: Option<usize>) -> Vec<usize> {
+        let mut rng = thread_rng();
+        let mut by_artist: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, artist) in self.artists.iter().enumerate() {
+            if Some(index) != first {
+                by_artist.entry(*artist).or_default().push(index);
+            }
+        }
+        let mut artists: Vec<(usize, Vec<usize>)> = by_artist.into_iter().collect();
+        artists.iter_mut().for_each(|(_, tracks)| tracks.shuffle(&mut rng));
+        let mut spread: Vec<usize> = first.into_iter().collect();
+        let mut last = first.or(previous).map(|i| self.artists[i]);
+        while !artists.is_empty() {
+            let left = self.ids.len() - spread.len();
+            let allowed: Vec<usize> = (0..artists.len()).filter(|a| Some(artists[*a].0) != last).collect();
+            let pick = match allowed.iter().copied().max_by_key(|a| artists[*a].1.len()) {
+                None => 0,
+                //an artist with more than half of whats left has to go now, later it couldnt be
+                //kept apart anymore
+                Some(biggest) if artists[biggest].1.len() * 2 > left => biggest,
+                Some(_) => {
+                    let mut n = rng.gen_range(0..allowed.iter().map(|a| artists[*a].1.len()).sum::<usize>());
+                    let mut pick = allowed[0];
+                    for a in allowed {
+                        let len = artists[a].1.len();
+                        if n < len {
+                            pick = a;
+                            break;
+                        }
+                        n -= len;
+                    }
+                    pick
+                }
+            };
+            let (artist, tracks) = &mut artists[pick];
+            spread.extend(tracks.pop());
+            last = Some(*artist);
+            if tracks.is_empty() {
+                artists.swap_remove(pick);
+            }
+        }
+        spread
+    }
+    //called whenever a track starts, what was playing before goes into the history
+    pub fn played(&mut self, id: TrackId) {
+        match self.current.replace(id) {
+            //repeat single would fill the history with one track
+            Some(previous) if previous != id => {
+                self.history.push_back(previous);
+                if self.history.len() > HISTORY_LEN {
+                    self.history.pop_front();
+                }
+            }
+            _ => (),
+        }
+    }
+    //goes back to what played before, or to the track before in the list when nothing did,
+    //going back doesnt add to the history so p can be pressed again and again
+    pub fn previous(&mut self) -> Option<usize> {
+        while let Some(id) = self.history.pop_back() {
+            //tracks whose files are gone are skipped
+            let Some(index) = self.index_of(id) else { continue };
+            self.current = Some(id);
+            self.position = self.position_of(index);
+            return Some(index);
+        }
+        self.position = match (self.position, self.repeat) {
+            (0, Repeat::All) => self.ids.len() - 1,
+            (0, _) => return None,
+            (position, _) => position - 1,
+        };
+        let index = self.index_at(self.position);
+        self.current = Some(self.ids[index]);
+        Some(index)
+    }
+}
+
+//returns a vec of random numbers, used to play random music
+fn shuffle_list(len: usize) -> Vec<usize> {
+    let mut shuffled: Vec<usize> = (0..len).collect();
+    shuffled.shuffle(&mut thread_rng());
+    shuffled
+}
+
+//the tracks of every album by disc and track number, an album is its tag and the dir its in so
+//two albums that are both called greatest hits stay apart, untagged files go by their dir
+fn albums(tracks: &[Track]) -> Vec<Vec<usize>> {
+    let mut albums: HashMap<(Option<&str>, Option<&Path>), Vec<usize>> = HashMap::new();
+    for (index, track) in tracks.iter().enumerate() {
+        albums.entry((track.album.as_deref(), track.path.parent())).or_default().push(index);
+    }
+    let mut albums: Vec<Vec<usize>> = albums.into_values().collect();
+    for album in &mut albums {
+        album.sort_by_key(|i| (tracks[*i].disc, tracks[*i].track_number, *i));
+    }
+    albums
+}
+
+//artists are told apart by name whatever the case, tracks without one get a number of their own
+fn artists(tracks: &[Track]) -> Vec<usize> {
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+    let artist = |t: &Track| t.artist.as_deref().map(|a| a.trim().to_lowercase()).filter(|a| !a.is_empty());
+    tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| match artist(track) {
+            Some(artist) => {
+                let next = tracks.len() + numbers.len();
+                *numbers.entry(artist).or_insert(next)
+            }
+            None => index,
+        })
+        .collect()
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::track::Track;
+
+//a line of a playlist file, with whatever the file said about it
+#[derive(Default)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl Entry {
+    //the file knows best what the track is called, anything it left out stays as probed
+    pub fn apply_to(&self, track: &mut Track) {
+        if let Some(title) = &self.title {
+            track.title = title.clone();
+        }
+        if let Some(artist) = &self.artist {
+            track.artist = Some(artist.clone());
+        }
+        if let Some(album) = &self.album {
+            track.album = Some(album.clone());
+        }
+        if track.duration.is_none() {
+            track.duration = self.duration;
+        }
+    }
+    //"artist - title" is how m3u and pls files name tracks
+    fn set_name(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        match name.split_once(" - ") {
+            Some((artist, title)) => {
+                self.artist = Some(artist.to_string());
+                self.title = Some(title.to_string());
+            }
+            None => self.title = Some(name.to_string()),
+        }
+    }
+}
+
+pub fn is_playlist(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("m3u" | "m3u8" | "pls" | "xspf"))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    Some(path.extension()?.to_str()?.to_lowercase())
+}
+
+//reads an m3u, pls or xspf playlist in its order, relative paths are relative to where it is
+pub fn load(path: &Path) -> Result<Vec<Entry>, io::Error> {
+    let bytes = fs::read(path)?;
+    //old .m3u files are often latin-1, their odd characters become � instead of failing
+    let text = String::from_utf8_lossy(&bytes);
+    //the bom some editors put in front of utf-8 files
+    let text = text.trim_start_matches('\u{feff}');
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(match extension(path).as_deref() {
+        Some("pls") => parse_pls(text, base),
+        Some("xspf") => parse_xspf(text, base),
+        _ => parse_m3u(&bytes, base),
+    })
+}
+
+//m3u has no fixed encoding, so only the comments are read as text and the paths keep their
+//bytes, a latin-1 file name in a latin-1 playlist still points at the file
+fn parse_m3u(bytes: &[u8], base: &Path) -> Vec<Entry> {
+    let bytes = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(bytes);
+    let mut entries = Vec::new();
+    let mut info: Option<Entry> = None;
+    for line in bytes.split(|b| *b == b'\n').map(<[u8]>::trim_ascii) {
+        if let Some(extinf) = line.strip_prefix(b"#EXTINF:") {
+            info = Some(parse_extinf(&String::from_utf8_lossy(extinf)));
+            continue;
+        }
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let entry = info.take().unwrap_or_default();
+        let path = match std::str::from_utf8(line) {
+            Ok(line) => resolve(line, base),
+            //not utf-8, so not a url either
+            Err(_) => base.join(OsStr::from_bytes(line)),
+        };
+        entries.push(Entry { path, ..entry });
+    }
+    entries
+}
+
+//"123 some=attribute,Artist - Title", -1 is an unknown length
+fn parse_extinf(extinf: &str) -> Entry {
+    let (head, name) = extinf.split_once(',').unwrap_or((extinf, ""));
+    let mut entry = Entry {
+        duration: head.split_whitespace().next().and_then(parse_secs),
+        ..Default::default()
+    };
+    entry.set_name(name);
+    entry
+}
+
+//an ini file, File1= Title1= Length1= and so on, the numbers give the order
+fn parse_pls(text: &str, base: &Path) -> Vec<Entry> {
+    let mut entries: BTreeMap<u32, Entry> = BTreeMap::new();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let key = key.trim().to_lowercase();
+        let field = key.trim_end_matches(|c: char| c.is_ascii_digit());
+        let Ok(number) = key[field.len()..].parse() else { continue };
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.path = resolve(value.trim(), base),
+            "title" => entry.set_name(value),
+            "length" => entry.duration = parse_secs(value.trim()),
+            _ => (),
+        }
+    }
+    //a title without a file has nothing to play
+    entries.into_values().filter(|e| !e.path.as_os_str().is_empty()).collect()
+}
+
+//only the bits of xml xspf needs, every <track> with its location and what it says about it
+fn parse_xspf(text: &str, base: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut rest = text;
+    while let Some(track) = next_element(&mut rest, "track") {
+        let mut inner = track;
+        //locations are uris, so relative ones are percent encoded as well
+        let Some(location) = next_element(&mut inner, "location") else { continue };
+        let location = xml_unescape(location.trim());
+        let path = match location.contains("://") {
+            true => resolve(&location, base),
+            false => base.join(percent_decode(&location)),
+        };
+        let field = |name| {
+            let mut inner = track;
+            next_element(&mut inner, name).map(|t| xml_unescape(t.trim())).filter(|t| !t.is_empty())
+        };
+        entries.push(Entry {
+            path,
+            title: field("title"),
+            artist: field("creator"),
+            album: field("album"),
+            //milliseconds
+            duration: field("duration").and_then(|d| d.parse().ok()).map(Duration::from_millis),
+        });
+    }
+    entries
+}
+
+//the content of the next <name>...</name> in s, s is moved past it
+fn next_element<'a>(s: &mut &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut from = 0;
+    loop {
+        let start = from + s[from..].find(&open)?;
+        let after = &s[start + open.len()..];
+        //<track> must not match <trackList>
+        if !after.starts_with(['>', ' ', '\t', '\n', '\r', '/']) {
+            from = start + open.len();
+            continue;
+        }
+        let content = start + open.len() + after.find('>')? + 1;
+        if s[..content].ends_with("/>") {
+            *s = &s[content..];
+            return Some("");
+        }
+        let end = content + s[content..].find(&close)?;
+        let found = &s[content..end];
+        *s = &s[end + close.len()..];
+        return Some(found);
+    }
+}
+
+//the five named entities and numeric ones
+fn xml_unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let decoded = match &rest[1..semi] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            n if n.starts_with("#x") => u32::from_str_radix(&n[2..], 16).ok().and_then(char::from_u32),
+            n if n.starts_with('#') => n[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+//-1, 1e30, inf and other nonsense is an unknown length
+fn parse_secs(s: &str) -> Option<Duration> {
+    s.parse::<f64>().ok().filter(|s| *s > 0.0).and_then(|s| Duration::try_from_secs_f64(s).ok())
+}
+
+//urls other than file:// are kept as they are, nothing can play them so they end up reported
+//as missing
+fn resolve(location: &str, base: &Path) -> PathBuf {
+    match location.strip_prefix("file://") {
+        //file://localhost/music/x.mp3 is the same as file:///music/x.mp3
+        Some(path) => PathBuf::from(percent_decode(path.strip_prefix("localhost").unwrap_or(path))),
+        None if location.contains("://") => PathBuf::from(location),
+        None => base.join(location),
+    }
+}
+
+//"%20" -> " ", invalid escapes are left alone
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+//writes an extended m3u8 with absolute paths, so it still works when moved, the paths go in
+//as the bytes the filesystem has even when they arent utf-8
+pub fn save(path: &Path, tracks: &[&Track]) -> Result<(), io::Error> {
+    let mut out = b"#EXTM3U\n".to_vec();
+    for track in tracks {
+        let secs = track.duration.map_or(-1, |d| d.as_secs() as i64);
+        out.extend_from_slice(format!("#EXTINF:{},{}\n", secs, track.display_name()).as_bytes());
+        out.extend_from_slice(track.path.as_os_str().as_bytes());
+        out.push(b'\n');
+    }
+    fs::write(path, out)
+}
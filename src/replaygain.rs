@@ -0,0 +1,105 @@
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+use crate::{config::Config, track::Track};
+
+//which of the stored gains gets used
+#[derive(Clone, Copy, Default)]
+pub enum GainMode {
+    #[default] Off,
+    Track,
+    Album,
+    //track gain while shuffling, album gain otherwise
+    Auto,
+}
+
+pub fn match_gain_mode(string: &str) -> Option<GainMode> {
+    match string {
+        "off" => Some(GainMode::Off),
+        "track" => Some(GainMode::Track),
+        "album" => Some(GainMode::Album),
+        "auto" => Some(GainMode::Auto),
+        _ => None,
+    }
+}
+
+//gains in dB and peaks as linear sample values, straight from the tags
+#[derive(Clone, Copy, Default)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    //picks up replaygain tags under their standard or raw names
+    pub fn apply_tag(&mut self, tag: &Tag) {
+        let value = tag.value.to_string();
+        //id3 "TXXX:replaygain_track_gain", mp4 "----:com.apple.iTunes:replaygain_track_gain"
+        let key = tag.key.rsplit(':').next().unwrap_or_default().to_lowercase();
+        match (tag.std_key, key.as_str()) {
+            (Some(StandardTagKey::ReplayGainTrackGain), _) | (_, "replaygain_track_gain") => {
+                self.track_gain = parse_db(&value)
+            }
+            (Some(StandardTagKey::ReplayGainTrackPeak), _) | (_, "replaygain_track_peak") => {
+                self.track_peak = value.trim().parse().ok()
+            }
+            (Some(StandardTagKey::ReplayGainAlbumGain), _) | (_, "replaygain_album_gain") => {
+                self.album_gain = parse_db(&value)
+            }
+            (Some(StandardTagKey::ReplayGainAlbumPeak), _) | (_, "replaygain_album_peak") => {
+                self.album_peak = value.trim().parse().ok()
+            }
+            _ => (),
+        }
+    }
+}
+
+//"-6.54 dB" -> -6.54
+fn parse_db(s: &str) -> Option<f32> {
+    s.trim().trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace()).parse().ok()
+}
+
+//turns the gain tags of a track into a multiplier for its samples
+pub struct Normalizer {
+    pub mode: GainMode,
+    pub preamp: f32,
+    pub prevent_clipping: bool,
+}
+
+impl Normalizer {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            mode: config.gain_mode,
+            preamp: config.preamp,
+            prevent_clipping: config.prevent_clipping,
+        }
+    }
+    //mixed is whether tracks of different albums play after each other
+    pub fn factor(&self, track: &Track, mixed: bool) -> f32 {
+        let rg = &track.replay_gain;
+        let album = match self.mode {
+            GainMode::Off => return 1.0,
+            GainMode::Track => false,
+            GainMode::Album => true,
+            GainMode::Auto => !mixed,
+        };
+        //fall back to the other kind when a file only has one of them
+        let (gain, peak) = if album {
+            (rg.album_gain.or(rg.track_gain), rg.album_peak.or(rg.track_peak))
+        } else {
+            (rg.track_gain.or(rg.album_gain), rg.track_peak.or(rg.album_peak))
+        };
+        //untagged files are left alone
+        let Some(gain) = gain else { return 1.0 };
+        let factor = db_to_linear(gain + self.preamp);
+        match peak {
+            Some(peak) if self.prevent_clipping && peak > 0.0 => factor.min(1.0 / peak),
+            _ => factor,
+        }
+    }
+}
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
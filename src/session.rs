@@ -0,0 +1,121 @@
+use std::{
+    fs, io,
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{
+    config::{data_dir, Config},
+    player::{Repeat, Shuffle},
+    track::TrackId,
+};
+
+//bumped whenever the lines change, an old file is then ignored
+const HEADER: &str = "bwmp session 1";
+
+//where playback was when bwmp quit, so the next start can carry on from there
+pub struct Session {
+    //the dirs or the playlist that were played, a session only resumes with the same ones
+    pub library: String,
+    pub track: Option<TrackId>,
+    pub position: Duration,
+    pub volume: f32,
+    pub repeat: Repeat,
+    pub shuffle: Shuffle,
+    //the shuffled order, every track of the library once
+    pub order: Vec<TrackId>,
+    pub queue: Vec<TrackId>,
+    pub history: Vec<TrackId>,
+    //the track the list cursor was on
+    pub selected: Option<TrackId>,
+}
+
+impl Session {
+    //None when there is no session or it was about a different library
+    pub fn load(library: &str) -> Option<Self> {
+        let text = fs::read_to_string(session_path()).ok()?;
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return None;
+        }
+        let mut session = Self {
+            library: String::new(),
+            track: None,
+            position: Duration::ZERO,
+            volume: 1.0,
+            repeat: Repeat::None,
+            shuffle: Shuffle::Off,
+            order: Vec::new(),
+            queue: Vec::new(),
+            history: Vec::new(),
+            selected: None,
+        };
+        for line in lines {
+            let (key, value) = line.split_once('\t').unwrap_or((line, ""));
+            let ids = || value.split_whitespace().filter_map(TrackId::from_hex).collect();
+            match key {
+                "library" => session.library = value.to_string(),
+                "track" => session.track = TrackId::from_hex(value),
+                "position" => session.position = value.parse().map(Duration::from_millis).unwrap_or_default(),
+                "volume" => session.volume = value.parse().unwrap_or(session.volume),
+                //written the way the ui shows them
+                "repeat" => {
+                    let modes = [Repeat::None, Repeat::Single, Repeat::All];
+                    session.repeat = modes.into_iter().find(|r| r.to_string() == value).unwrap_or_default();
+                }
+                "shuffle" => {
+                    let modes = [Shuffle::Off, Shuffle::Tracks, Shuffle::Albums, Shuffle::Spread];
+                    session.shuffle = modes.into_iter().find(|s| s.to_string() == value).unwrap_or_default();
+                }
+                "order" => session.order = ids(),
+                "queue" => session.queue = ids(),
+                "history" => session.history = ids(),
+                "selected" => session.selected = TrackId::from_hex(value),
+                _ => (),
+            }
+        }
+        (session.library == library).then_some(session)
+    }
+    pub fn save(&self) -> Result<(), io::Error> {
+        fs::create_dir_all(data_dir())?;
+        let ids = |ids: &[TrackId]| ids.iter().map(|id| id.to_hex()).collect::<Vec<String>>().join(" ");
+        //written next to the old one and moved over it, like the library
+        let tmp = session_path().with_extension("tmp");
+        let mut file = io::BufWriter::new(fs::File::create(&tmp)?);
+        writeln!(file, "{HEADER}")?;
+        writeln!(file, "library\t{}", self.library)?;
+        if let Some(track) = self.track {
+            writeln!(file, "track\t{}", track.to_hex())?;
+        }
+        writeln!(file, "position\t{}", self.position.as_millis())?;
+        writeln!(file, "volume\t{}", self.volume)?;
+        writeln!(file, "repeat\t{}", self.repeat)?;
+        writeln!(file, "shuffle\t{}", self.shuffle)?;
+        writeln!(file, "order\t{}", ids(&self.order))?;
+        writeln!(file, "queue\t{}", ids(&self.queue))?;
+        writeln!(file, "history\t{}", ids(&self.history))?;
+        if let Some(selected) = self.selected {
+            writeln!(file, "selected\t{}", selected.to_hex())?;
+        }
+        file.flush()?;
+        fs::rename(tmp, session_path())
+    }
+}
+
+fn session_path() -> PathBuf {
+    data_dir().join("session")
+}
+
+//the playlist or the dirs, canonicalized so -d ~/music and -d /home/someone/music are the same
+pub fn library_key(config: &Config) -> String {
+    let paths = match &config.playlist {
+        Some(playlist) => vec![playlist.clone()],
+        None => config.dirs.clone(),
+    };
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|p| fs::canonicalize(p).unwrap_or(p.clone()).to_string_lossy().into_owned())
+        .collect();
+    paths.join("\t")
+}
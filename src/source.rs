@@ -0,0 +1,226 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+use crate::decoder::TrackDecoder;
+
+//shared between the player and the source that is playing inside the sink
+#[derive(Default)]
+struct TrackState {
+    //samples the sink has pulled out of the decoder, counted from the start of the track
+    samples: AtomicU64,
+    channels: AtomicU16,
+    sample_rate: AtomicU32,
+    finished: AtomicBool,
+    cancelled: AtomicBool,
+    seek_pending: AtomicBool,
+    seek_to: Mutex<Option<Duration>>,
+    fade_pending: AtomicBool,
+    fade_out: Mutex<Option<Duration>>,
+}
+
+//the player side of a tracked source
+#[derive(Clone, Default)]
+pub struct TrackHandle(Arc<TrackState>);
+
+impl TrackHandle {
+    //how far into the track the audio output actually is
+    pub fn position(&self) -> Duration {
+        //a paused sink doesnt pull samples, so a seek can wait a while before it happens
+        if self.0.seek_pending.load(Ordering::Acquire) {
+            if let Some(to) = *self.0.seek_to.lock().unwrap() {
+                return to;
+            }
+        }
+        let samples = self.0.samples.load(Ordering::Relaxed);
+        let per_sec = self.0.channels.load(Ordering::Relaxed) as u64
+            * self.0.sample_rate.load(Ordering::Relaxed) as u64;
+        if per_sec == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs(samples / per_sec)
+            + Duration::from_nanos((samples % per_sec) * 1_000_000_000 / per_sec)
+    }
+    //true once the decoder ran out of samples
+    pub fn is_finished(&self) -> bool {
+        self.0.finished.load(Ordering::Relaxed)
+    }
+    //true once the sink pulled the first sample
+    pub fn has_started(&self) -> bool {
+        self.0.samples.load(Ordering::Relaxed) > 0
+    }
+    //makes the source end right away, used to take back a track that was queued in a sink
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+    //fades the track out and then ends it, used for crossfades and skips
+    pub fn fade_out(&self, over: Duration) {
+        *self.0.fade_out.lock().unwrap() = Some(over);
+        self.0.fade_pending.store(true, Ordering::Release);
+    }
+    //the seek happens in the audio thread the next time it asks for a sample
+    pub fn seek(&self, to: Duration) {
+        *self.0.seek_to.lock().unwrap() = Some(to);
+        self.0.seek_pending.store(true, Ordering::Release);
+    }
+}
+
+//a volume ramp, counted in samples since the source started playing
+struct Fade {
+    start: u64,
+    len: u64,
+    out: bool,
+}
+
+impl Fade {
+    //equal power curve, so a crossfade doesnt dip in loudness halfway through
+    fn gain(&self, emitted: u64) -> f32 {
+        let t = (emitted.saturating_sub(self.start) as f32 / self.len.max(1) as f32).min(1.0);
+        let angle = t * std::f32::consts::FRAC_PI_2;
+        if self.out {
+            angle.cos()
+        } else {
+            angle.sin()
+        }
+    }
+    fn is_done(&self, emitted: u64) -> bool {
+        emitted >= self.start + self.len
+    }
+}
+
+//counts every sample that goes through it, so the position comes from the audio and not a clock
+pub struct Tracked {
+    inner: TrackDecoder,
+    state: Arc<TrackState>,
+    samples: u64,
+    //unlike samples this isnt moved by seeking, fades are timed with it
+    emitted: u64,
+    fade: Option<Fade>,
+    //replaygain, applied under the sink volume
+    gain: f32,
+    //told when the first sample goes out, the player switches to the track on that
+    on_start: Option<Sender<()>>,
+}
+
+impl Tracked {
+    pub fn new(inner: TrackDecoder) -> (Self, TrackHandle) {
+        let handle = TrackHandle::default();
+        handle.0.channels.store(inner.channels(), Ordering::Relaxed);
+        handle.0.sample_rate.store(inner.sample_rate(), Ordering::Relaxed);
+        let selfish = Self {
+            inner,
+            state: handle.0.clone(),
+            samples: 0,
+            emitted: 0,
+            fade: None,
+            gain: 1.0,
+            on_start: None,
+        };
+        (selfish, handle)
+    }
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+    //sends on the channel once the sink starts pulling samples out of the track
+    pub fn notify_start(mut self, on_start: Sender<()>) -> Self {
+        self.on_start = Some(on_start);
+        self
+    }
+    //starts the track silent and brings it up to full volume
+    pub fn fade_in(mut self, over: Duration) -> Self {
+        if !over.is_zero() {
+            self.fade = Some(Fade { start: 0, len: self.samples_in(over), out: false });
+        }
+        self
+    }
+    fn samples_in(&self, d: Duration) -> u64 {
+        let per_sec = self.inner.channels() as u128 * self.inner.sample_rate() as u128;
+        (d.as_nanos() * per_sec / 1_000_000_000) as u64
+    }
+    fn apply_fade_out(&mut self) {
+        let Some(over) = self.state.fade_out.lock().unwrap().take() else { return };
+        //fading out in the middle of a fade in starts from the current volume
+        let gain = self.fade.as_ref().map(|f| f.gain(self.emitted)).unwrap_or(1.0);
+        let len = self.samples_in(over);
+        let already = ((1.0 - gain) * len as f32) as u64;
+        self.fade = Some(Fade { start: self.emitted.saturating_sub(already), len, out: true });
+    }
+    fn apply_seek(&mut self) {
+        let Some(to) = self.state.seek_to.lock().unwrap().take() else { return };
+        if self.inner.seek(to) {
+            let frames = to.as_nanos() * self.inner.sample_rate() as u128 / 1_000_000_000;
+            self.samples = frames as u64 * self.inner.channels() as u64;
+            self.state.samples.store(self.samples, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Iterator for Tracked {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.state.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        //only seek or start fading between frames
+        if self.samples.is_multiple_of(self.inner.channels().max(1) as u64) {
+            if self.state.seek_pending.swap(false, Ordering::Acquire) {
+                self.apply_seek();
+            }
+            if self.state.fade_pending.swap(false, Ordering::Acquire) {
+                self.apply_fade_out();
+            }
+            match &self.fade {
+                Some(f) if f.out && f.is_done(self.emitted) => {
+                    self.state.finished.store(true, Ordering::Relaxed);
+                    return None;
+                }
+                Some(f) if f.is_done(self.emitted) => self.fade = None,
+                _ => (),
+            }
+        }
+        match self.inner.next() {
+            Some(sample) => {
+                self.samples += 1;
+                self.state.samples.store(self.samples, Ordering::Relaxed);
+                if let Some(on_start) = self.on_start.take() {
+                    //the player may be gone already when the program quits
+                    let _ = on_start.send(());
+                }
+                let gain = match &self.fade {
+                    Some(fade) => fade.gain(self.emitted) * self.gain,
+                    None => self.gain,
+                };
+                self.emitted += 1;
+                Some(sample * gain)
+            }
+            None => {
+                self.state.finished.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+impl Source for Tracked {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
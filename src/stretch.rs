@@ -0,0 +1,208 @@
+use std::{
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 3.0;
+
+//playback rate shared with every stretcher in the audio thread, stored as f32 bits
+#[derive(Clone)]
+pub struct SpeedControl(Arc<AtomicU32>);
+
+impl SpeedControl {
+    pub fn new(speed: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(speed.to_bits())))
+    }
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, speed: f32) {
+        self.0.store(speed.to_bits(), Ordering::Relaxed);
+    }
+}
+
+//where the splicing is at, indexes are frames from the start of the input buffer
+struct Splice {
+    //start of what would naturally follow the last piece that was output
+    natural: usize,
+    //where the next piece should come from if there was no searching, moves by half * speed
+    nominal: f64,
+}
+
+//wsola time stretching, changes the speed without changing the pitch by cutting the input into
+//overlapping pieces and spacing them out differently, each piece is moved a bit so it lines up
+//with what came before it
+pub struct Stretch<S> {
+    inner: S,
+    speed: SpeedControl,
+    channels: usize,
+    //half a window in frames, also how much gets output per piece
+    half: usize,
+    //how far a piece can be moved to line it up
+    tolerance: usize,
+    //rising half of a hann window, the falling half is 1 - rise
+    rise: Vec<f32>,
+    input: Vec<f32>,
+    inner_done: bool,
+    //None plays the input as it is
+    splice: Option<Splice>,
+    out: Vec<f32>,
+    out_pos: usize,
+    //channel of the next sample when passing through, the speed only changes between frames
+    channel: usize,
+}
+
+impl<S: Source<Item = f32>> Stretch<S> {
+    pub fn new(inner: S, speed: SpeedControl) -> Self {
+        let rate = inner.sample_rate() as usize;
+        //20ms half windows, short enough for speech and long enough for music
+        let half = (rate / 50).max(1);
+        let rise = (0..half)
+            .map(|i| (PI * i as f32 / (2 * half) as f32).sin().powi(2))
+            .collect();
+        Self {
+            channels: inner.channels().max(1) as usize,
+            inner,
+            speed,
+            half,
+            tolerance: rate / 100,
+            rise,
+            input: Vec::new(),
+            inner_done: false,
+            splice: None,
+            out: Vec::new(),
+            out_pos: 0,
+            channel: 0,
+        }
+    }
+    fn frames(&self) -> usize {
+        self.input.len() / self.channels
+    }
+    //reads from the inner source until the buffer holds enough frames or it runs out
+    fn read_until(&mut self, frames: usize) -> bool {
+        while self.frames() < frames && !self.inner_done {
+            match self.inner.next() {
+                Some(sample) => self.input.push(sample),
+                None => {
+                    self.inner_done = true;
+                    //a half frame at the end would shift every channel after it
+                    self.input.truncate(self.frames() * self.channels);
+                }
+            }
+        }
+        self.frames() >= frames
+    }
+    //the channels of one frame added together, good enough for lining pieces up
+    fn mono(&self, frame: usize) -> f32 {
+        self.input[frame * self.channels..(frame + 1) * self.channels].iter().sum()
+    }
+    //how alike the piece at `at` is to what naturally follows the last one
+    fn similarity(&self, natural: usize, at: usize) -> f32 {
+        let (mut dot, mut energy) = (0.0, 0.0);
+        for i in (0..self.half).step_by(2) {
+            let candidate = self.mono(at + i);
+            dot += self.mono(natural + i) * candidate;
+            energy += candidate * candidate;
+        }
+        dot / energy.sqrt().max(1e-6)
+    }
+    //coarse search first, then the frames around the best coarse match
+    fn best_position(&self, natural: usize, low: usize, high: usize) -> usize {
+        let best_of = |positions: &mut dyn Iterator<Item = usize>| {
+            positions
+                .map(|p| (p, self.similarity(natural, p)))
+                .fold((low, f32::MIN), |best, x| if x.1 > best.1 { x } else { best })
+                .0
+        };
+        let coarse = best_of(&mut (low..=high).step_by(4));
+        best_of(&mut (coarse.saturating_sub(3).max(low)..=(coarse + 3).min(high)))
+    }
+    //makes the next half window of output, or goes back to passing through
+    fn splice_next(&mut self, speed: f32) {
+        let Some(splice) = &self.splice else { return };
+        let (natural, nominal) = (splice.natural, splice.nominal);
+        let nominal_frame = nominal as usize;
+        let needed = (natural + self.half).max(nominal_frame + self.tolerance + self.half);
+        //at normal speed the natural continuation is exactly the input, so passing through
+        //from there is seamless, same when the track is ending
+        if speed == 1.0 || !self.read_until(needed) {
+            self.input.drain(..natural * self.channels);
+            self.splice = None;
+            return;
+        }
+        let low = nominal_frame.saturating_sub(self.tolerance);
+        let at = self.best_position(natural, low, nominal_frame + self.tolerance);
+        for i in 0..self.half {
+            let rise = self.rise[i];
+            for c in 0..self.channels {
+                let old = self.input[(natural + i) * self.channels + c];
+                let new = self.input[(at + i) * self.channels + c];
+                self.out.push(old * (1.0 - rise) + new * rise);
+            }
+        }
+        let natural = at + self.half;
+        let nominal = nominal + self.half as f64 * speed as f64;
+        //drop what no later piece can reach
+        let keep = natural.min((nominal as usize).saturating_sub(self.tolerance));
+        self.input.drain(..keep * self.channels);
+        self.splice = Some(Splice { natural: natural - keep, nominal: nominal - keep as f64 });
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Stretch<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if let Some(&sample) = self.out.get(self.out_pos) {
+                self.out_pos += 1;
+                return Some(sample);
+            }
+            self.out.clear();
+            self.out_pos = 0;
+            if self.splice.is_some() {
+                self.splice_next(self.speed.get());
+                continue;
+            }
+            //leftovers from splicing go out first
+            if !self.input.is_empty() {
+                std::mem::swap(&mut self.out, &mut self.input);
+                continue;
+            }
+            if self.channel == 0 && !self.inner_done && self.speed.get() != 1.0 {
+                self.splice = Some(Splice { natural: 0, nominal: 0.0 });
+                continue;
+            }
+            self.channel = (self.channel + 1) % self.channels;
+            return self.inner.next();
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Stretch<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.out.len() - self.out_pos {
+            0 if self.input.is_empty() && self.splice.is_none() => self.inner.current_frame_len(),
+            //more is coming from the buffer, at least a frame of it
+            0 => Some(self.channels),
+            left => Some(left),
+        }
+    }
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    //depends on a speed that can change at any time
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
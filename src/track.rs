@@ -0,0 +1,178 @@
+use std::{
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+use crate::{formats, replaygain::ReplayGain};
+
+//names a track for as long as its file stays where it is, so the queue and history still point
+//at the right songs after a rescan reorders the list
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TrackId(u64);
+
+impl TrackId {
+    //64 bit fnv-1a of the path, unlike std's hasher it wont change between rust versions
+    pub fn of(path: &Path) -> Self {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in path.as_os_str().as_bytes() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Self(hash)
+    }
+    //a song of a cue sheet, the file is shared so the number has to go in as well
+    pub fn of_cue(path: &Path, number: u32) -> Self {
+        Self::of(path).with(b'#', number)
+    }
+    //a file a playlist lists more than once, every time after the first gets its own id so each
+    //one has its own place in the order, the queue and the history
+    pub fn of_repeat(path: &Path, repeat: u32) -> Self {
+        Self::of(path).with(b'*', repeat)
+    }
+    //carries on hashing after the path, the separator keeps the kinds of ids apart
+    fn with(self, separator: u8, number: u32) -> Self {
+        let Self(hash) = self;
+        let mut hash = hash ^ separator as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        for byte in number.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        Self(hash)
+    }
+    //ids are written out as hex, for the session file
+    pub fn to_hex(self) -> String {
+        format!("{:016x}", self.0)
+    }
+    pub fn from_hex(s: &str) -> Option<Self> {
+        u64::from_str_radix(s, 16).ok().map(Self)
+    }
+}
+
+//where a song of a cue sheet is in its file, end is None for the last one
+#[derive(Clone, Copy)]
+pub struct CueRange {
+    pub number: u32,
+    pub start: Duration,
+    pub end: Option<Duration>,
+}
+
+//everything we know about a single song
+#[derive(Clone, Default)]
+pub struct Track {
+    pub id: TrackId,
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub duration: Option<Duration>,
+    pub replay_gain: ReplayGain,
+    //set once opening or decoding it failed, it gets skipped from then on
+    pub broken: bool,
+    //only a part of the file plays, the track came from a cue sheet
+    pub cue: Option<CueRange>,
+}
+
+impl Track {
+    //reads the embedded tags, anything missing falls back to what the filename says
+    pub fn from_path(path: &Path) -> Self {
+        let mut track = Self::from_filename(path);
+        if let Some(mut probed) = formats::probe(path) {
+            track.duration = formats::duration_of(probed.format.as_ref());
+            //id3v2 is read before the container, vorbis comments and mp4 atoms are part of it
+            if let Some(rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+                track.apply_tags(rev.tags());
+            }
+            if let Some(rev) = probed.format.metadata().current() {
+                track.apply_tags(rev.tags());
+            }
+        }
+        if track.duration.is_none() {
+            track.duration = formats::fallback_duration(path);
+        }
+        track
+    }
+    pub fn from_filename(path: &Path) -> Self {
+        Self {
+            id: TrackId::of(path),
+            path: path.to_path_buf(),
+            title: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+    fn apply_tags(&mut self, tags: &[Tag]) {
+        for tag in tags {
+            self.replay_gain.apply_tag(tag);
+            //riff info strings come nul terminated
+            let value = tag.value.to_string();
+            let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => self.title = value,
+                Some(StandardTagKey::Artist) => self.artist = Some(value),
+                //only used when there is no track artist
+                Some(StandardTagKey::AlbumArtist) => {
+                    self.artist.get_or_insert(value);
+                }
+                Some(StandardTagKey::Album) => self.album = Some(value),
+                Some(StandardTagKey::TrackNumber) => self.track_number = leading_number(&value),
+                Some(StandardTagKey::DiscNumber) => self.disc = leading_number(&value),
+                Some(StandardTagKey::Date) => self.year = leading_number(&value),
+                Some(StandardTagKey::Genre) => self.genre = Some(value),
+                _ => (),
+            }
+        }
+    }
+    //what gets shown in the list, "artist - title" or just the title
+    pub fn display_name(&self) -> String {
+        match &self.artist {
+            Some(artist) => format!("{} - {}", artist, self.title),
+            None => self.title.clone(),
+        }
+    }
+    //what gets shown after "Now playing:", "artist - title (album, disc 2, track 3, 2004)"
+    pub fn long_name(&self) -> String {
+        let mut extra: Vec<String> = Vec::new();
+        if let Some(album) = &self.album {
+            extra.push(album.clone());
+        }
+        if let Some(disc) = self.disc.filter(|d| *d > 1) {
+            extra.push(format!("disc {}", disc));
+        }
+        if let Some(number) = self.track_number {
+            extra.push(format!("track {}", number));
+        }
+        if let Some(year) = self.year {
+            extra.push(year.to_string());
+        }
+        if extra.is_empty() {
+            return self.display_name();
+        }
+        format!("{} ({})", self.display_name(), extra.join(", "))
+    }
+    //search_str has to be lowercase already
+    pub fn matches(&self, search_str: &str) -> bool {
+        [Some(&self.title), self.artist.as_ref(), self.album.as_ref(), self.genre.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|s| s.to_lowercase().contains(search_str))
+    }
+}
+
+//"3/12" -> 3, "2004-05-01" -> 2004
+fn leading_number(s: &str) -> Option<u32> {
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
@@ -1,26 +1,41 @@
 use std::{
-    collections::VecDeque,
     io::{stdout, Write},
-    path::PathBuf,
     time::Duration,
 };
 
 use termion::color::{self, Bg, Fg};
 use termion::cursor::{DetectCursorPos, Goto};
 
-use crate::{colors::Colors, misc::clear_term, misc::cut_string, player::Repeat};
+use crate::{colors::Colors, eq::EqControl, misc::clear_term, misc::cut_string, player::{Repeat, Shuffle}, track::Track};
 
 const RESET_FG: Fg<color::Reset> = color::Fg(color::Reset);
 const RESET_BG: Bg<color::Reset> = color::Bg(color::Reset);
 
+//what the list area shows, tab goes through them in this order
+#[derive(Clone, Copy, PartialEq)]
+pub enum Panel {
+    List,
+    Queue,
+    History,
+}
+
 pub struct Ui {
     stdout: std::io::Stdout,
     colors: Colors,
-    lines: u16,
-    offset: u16,
-    cursor_pos: u16,
-    pub now_playing: u16,
+    lines: usize,
+    //first list index on screen
+    offset: usize,
+    //selected row, 1 based, 0 is nothing selected
+    cursor_pos: usize,
+    pub now_playing: usize,
     music_list_names: Vec<String>,
+    //list indexes of what is queued and what played, newest first, the panels show them
+    queue: Vec<usize>,
+    history: Vec<usize>,
+    panel: Panel,
+    //selected panel entry and the first one on screen, both 0 based
+    panel_cursor: usize,
+    panel_offset: usize,
     term_size: [u16; 2],
     name_coords: [u16; 2],
     status_coords: [u16; 2],
@@ -28,31 +43,30 @@ pub struct Ui {
     shuffle_coords: [u16; 2],
     volume_coords: [u16; 2],
     queue_coords: [u16; 2],
+    eq_coords: [u16; 2],
+    message_coords: [u16; 2],
     bar_coords: [u16; 2],
 }
 
 impl Ui {
     //init the ui; I'm sooo fucking sorry
-    pub fn new(music_list: Vec<PathBuf>) -> Self {
+    pub fn new(music_list: &[Track]) -> Self {
         let mut stdout = stdout();
         let colors = Colors::new_from_config().unwrap_or(Colors::new_default());
         let (term_x, term_y) = termion::terminal_size().unwrap();
-        let l: u16 = if music_list.len() > (term_y - 7).into() {
-            term_y - 7
+        let l: u16 = if music_list.len() > (term_y - 8).into() {
+            term_y - 8
         } else {
             music_list.len().try_into().unwrap() //shouldnt panic (well it will if your terminal
                                                  //has more than 2^16 lines, but how would you even do that)
         };
-        let mls: Vec<String> = music_list
-            .iter()
-            .map(|p| cut_string(p.file_name().unwrap().to_string_lossy().to_string(), (term_x - 8).into()))
-            .collect();
+        let mls: Vec<String> = music_list.iter().map(|t| list_name(t, term_x)).collect();
         clear_term();
         print!("{}", Goto(1, 1));
         draw_horizontal_lines(term_x, "¯");
         init_music_list(&mls, l);
         print!("\n\r");
-        print!("{}", Goto(1, term_y - 5));
+        print!("{}", Goto(1, term_y - 6));
         draw_horizontal_lines(term_x, "-");
         print!("\n\r {}Now playing: ", Fg(colors.name));
         let (name_x, name_y) = stdout.cursor_pos().unwrap();
@@ -64,14 +78,19 @@ impl Ui {
         print!("None    ");
         print!("{}Shuffle: ", Fg(colors.shuffle));
         let (shuffle_x, shuffle_y) = stdout.cursor_pos().unwrap();
-        print!("false  ");
+        print!("Off     ");
         print!("{}Volume: ", Fg(colors.volume));
         let (vol_x, vol_y) = stdout.cursor_pos().unwrap();
         print!("100  ");
         let (queue_x, queue_y) = stdout.cursor_pos().unwrap();
         print!("{}Queue: ", Fg(colors.queue));
         print!("empty");
-        print!("\r\n\n ");
+        print!("\r\n ");
+        let (eq_x, eq_y) = stdout.cursor_pos().unwrap();
+        print!("{}EQ: off", Fg(colors.status));
+        print!("\r\n ");
+        let (message_x, message_y) = stdout.cursor_pos().unwrap();
+        print!("\r\n ");
         let (bar_x, bar_y) = stdout.cursor_pos().unwrap();
         print!("\r\n{}", RESET_FG);
         draw_horizontal_lines(term_x, "_");
@@ -79,10 +98,15 @@ impl Ui {
         stdout.flush().unwrap();
 
         Self {
-            stdout: stdout,
-            colors: colors,
+            stdout,
+            colors,
             music_list_names: mls,
-            lines: l,
+            queue: Vec::new(),
+            history: Vec::new(),
+            panel: Panel::List,
+            panel_cursor: 0,
+            panel_offset: 0,
+            lines: l.into(),
             offset: 0,
             cursor_pos: 0,
             now_playing: 0,
@@ -93,6 +117,8 @@ impl Ui {
             shuffle_coords: [shuffle_x, shuffle_y],
             volume_coords: [vol_x, vol_y],
             queue_coords: [queue_x, queue_y],
+            eq_coords: [eq_x, eq_y],
+            message_coords: [message_x, message_y],
             bar_coords: [bar_x, bar_y],
         }
     }
@@ -132,7 +158,7 @@ impl Ui {
         self.stdout.flush().unwrap();
     }
     //changes the shuffle status
-    pub fn update_shuffle(&mut self, s: bool) {
+    pub fn update_shuffle(&mut self, s: Shuffle) {
         self.clear_area(
             self.shuffle_coords[0],
             self.shuffle_coords[1],
@@ -179,7 +205,12 @@ impl Ui {
         );
         self.stdout.flush().unwrap();
     }
-    pub fn update_queue(&mut self, q: &VecDeque<u16>) {
+    //takes the list numbers of the queued tracks, 1 based like on screen
+    pub fn update_queue(&mut self, q: &[usize]) {
+        self.queue = q.iter().map(|n| n - 1).collect();
+        if self.panel == Panel::Queue {
+            self.draw_panel();
+        }
         self.clear_area(
             self.queue_coords[0],
             self.queue_coords[1],
@@ -195,9 +226,91 @@ impl Ui {
         );
         self.stdout.flush().unwrap();
     }
-    //updates the time bar
-    pub fn update_bar(&mut self, duration: Duration, time: Duration) {
-        let percent = (time.as_millis() * 100) / duration.as_millis();
+    //the eq line, shows the preset and its bands so you know what you switched to
+    pub fn update_eq(&mut self, eq: &EqControl) {
+        self.clear_area(
+            self.eq_coords[0],
+            self.eq_coords[1],
+            self.term_size[0] - 1,
+            self.eq_coords[1],
+        );
+        let line = match eq.preset() {
+            Some(preset) => {
+                let bands = preset.bands.iter().map(|b| format!("{b}")).collect::<Vec<_>>().join(" | ");
+                format!(
+                    "EQ: {} {}  {}",
+                    if eq.is_enabled() { "on " } else { "off" },
+                    preset.name,
+                    bands
+                )
+            }
+            None => "EQ: no presets".to_string(),
+        };
+        print!(
+            "{}{}{}",
+            Goto(self.eq_coords[0], self.eq_coords[1]),
+            Fg(self.colors.status),
+            cut_string(line, (self.term_size[0] - 3).into())
+        );
+        self.stdout.flush().unwrap();
+    }
+    //the status line under the eq, for things that went wrong
+    pub fn show_message(&mut self, message: &str) {
+        self.clear_area(
+            self.message_coords[0],
+            self.message_coords[1],
+            self.term_size[0] - 1,
+            self.message_coords[1],
+        );
+        print!(
+            "{}{}{}",
+            Goto(self.message_coords[0], self.message_coords[1]),
+            Fg(self.colors.status),
+            cut_string(message.into(), (self.term_size[0] - 3).into())
+        );
+        self.stdout.flush().unwrap();
+    }
+    //tags a track in the list as unplayable
+    pub fn mark_broken(&mut self, index: usize) {
+        let name = &mut self.music_list_names[index];
+        *name = cut_string(format!("[broken] {name}"), (self.term_size[0] - 8).into());
+        self.scroll_music_list();
+        self.select_track();
+    }
+    //swaps in the list after the library changed on disk, the selection stays on the same track
+    //and row if it can, playing is None when its file is gone
+    pub fn set_tracks(&mut self, music_list: &[Track], selected: Option<usize>, playing: Option<usize>) {
+        let old_lines = self.lines;
+        self.music_list_names = music_list.iter().map(|t| list_name(t, self.term_size[0])).collect();
+        let len = self.music_list_names.len();
+        self.lines = len.min((self.term_size[1] - 8).into());
+        //one past the end is never on screen, so nothing gets highlighted
+        self.now_playing = playing.unwrap_or(len);
+        match selected {
+            Some(index) => {
+                let row = self.cursor_pos.clamp(1, self.lines);
+                self.offset = (index + 1).saturating_sub(row).min(len - self.lines);
+                self.cursor_pos = index + 1 - self.offset;
+            }
+            None => {
+                self.offset = self.offset.min(len - self.lines);
+                self.cursor_pos = self.cursor_pos.min(self.lines);
+            }
+        }
+        //the queue and history still point into the old list, the player sends them again right
+        //after this and an open panel gets drawn then
+        self.queue.clear();
+        self.history.clear();
+        if self.panel != Panel::List {
+            return;
+        }
+        self.clear_area(3, 2, self.term_size[0] - 1, old_lines.max(self.lines) as u16 + 1);
+        self.scroll_music_list();
+        self.select_track();
+    }
+    //updates the time bar, the times are how long it takes at the current speed
+    pub fn update_bar(&mut self, duration: Duration, time: Duration, speed: f32) {
+        let percent = (time.as_millis() * 100).checked_div(duration.as_millis()).unwrap_or(0);
         print!("{}", Goto(self.bar_coords[0], self.bar_coords[1]));
         for space in 2..self.term_size[0] {
             let a: u128 = ((space * 100) / self.term_size[0]).into();
@@ -212,100 +325,185 @@ impl Ui {
             "{}{}-{}/{}-",
             Goto((self.term_size[0] / 2) - 6, self.bar_coords[1]),
             termion::color::Fg(self.colors.bar_fg),
-            format_time(time),
-            format_time(duration)
+            format_time(time.div_f32(speed)),
+            format_time(duration.div_f32(speed))
         );
+        if speed != 1.0 {
+            print!("x{speed}");
+        }
+        self.stdout.flush().unwrap();
+    }
+    //same as update_queue, the most recent first
+    pub fn update_history(&mut self, h: &[usize]) {
+        self.history = h.iter().map(|n| n - 1).collect();
+        if self.panel == Panel::History {
+            self.draw_panel();
+        }
+    }
+    pub fn panel(&self) -> Panel {
+        self.panel
+    }
+    //switches the list area to the next panel, list -> queue -> history -> list
+    pub fn next_panel(&mut self) {
+        self.show_panel(match self.panel {
+            Panel::List => Panel::Queue,
+            Panel::Queue => Panel::History,
+            Panel::History => Panel::List,
+        });
+    }
+    fn show_panel(&mut self, panel: Panel) {
+        self.panel = panel;
+        self.panel_cursor = 0;
+        self.panel_offset = 0;
+        self.clear_area(3, 2, self.term_size[0] - 1, self.term_size[1] - 7);
+        if panel == Panel::List {
+            self.scroll_music_list();
+            self.select_track();
+        } else {
+            self.draw_panel();
+        }
+    }
+    //the selected queue or history entry, 0 based
+    pub fn get_panel_selected(&self) -> usize {
+        self.panel_cursor
+    }
+    pub fn select_in_panel(&mut self, position: usize) {
+        self.panel_cursor = position;
+        self.draw_panel();
+    }
+    fn draw_panel(&mut self) {
+        let (entries, empty) = match self.panel {
+            Panel::List => return,
+            Panel::Queue => (&self.queue, "queue is empty"),
+            Panel::History => (&self.history, "nothing played yet"),
+        };
+        let rows: usize = (self.term_size[1] - 8).into();
+        self.panel_cursor = self.panel_cursor.min(entries.len().saturating_sub(1));
+        //keeps the selected entry on screen
+        if self.panel_cursor < self.panel_offset {
+            self.panel_offset = self.panel_cursor;
+        } else if self.panel_cursor >= self.panel_offset + rows {
+            self.panel_offset = self.panel_cursor + 1 - rows;
+        }
+        self.panel_offset = self.panel_offset.min(entries.len().saturating_sub(rows));
+        let lines: Vec<String> = (self.panel_offset..entries.len())
+            .take(rows)
+            .map(|position| {
+                let name = &self.music_list_names[entries[position]];
+                if position == self.panel_cursor {
+                    format!("{}{}. {}", Fg(self.colors.selected), position + 1, name)
+                } else {
+                    format!("{}{}.{}", RESET_FG, position + 1, name)
+                }
+            })
+            .collect();
+        self.clear_area(3, 2, self.term_size[0] - 1, rows as u16 + 1);
+        if lines.is_empty() {
+            print!("{}{}{}", Goto(3, 2), RESET_FG, empty);
+        }
+        for (row, line) in lines.iter().enumerate() {
+            print!("{}{}", Goto(3, row as u16 + 2), line);
+        }
         self.stdout.flush().unwrap();
     }
-    pub fn get_selected(&self) -> u16 {
+    pub fn get_selected(&self) -> usize {
         self.cursor_pos + self.offset
     }
     pub fn scroll_music_list(&mut self) {
-        self.clear_area(3, 2, self.term_size[0] - 1, self.lines + 1);
+        if self.panel != Panel::List {
+            return;
+        }
+        self.clear_area(3, 2, self.term_size[0] - 1, self.lines as u16 + 1);
         for i in 0..self.lines {
-            print!("{}", Goto(3, i + 2));
+            print!("{}", Goto(3, i as u16 + 2));
             print!(
                 "{}{}.{}",
                 RESET_FG,
                 (i + 1 + self.offset),
-                self.music_list_names[(i + self.offset) as usize]
+                self.music_list_names[i + self.offset]
             );
         }
         self.highlight_playing(self.now_playing);
     }
     pub fn dehighlight_playing(&self) {
+        if self.panel != Panel::List {
+            return;
+        }
         if self.now_playing < self.offset || self.now_playing > (self.lines + self.offset - 1) {
             return;
         }
-        print!("{}", Goto(3, self.now_playing - self.offset + 2));
+        print!("{}", Goto(3, (self.now_playing - self.offset) as u16 + 2));
         print!(
             "{}{}.{}  ",
             RESET_FG,
             self.now_playing + 1,
-            self.music_list_names[self.now_playing as usize]
+            self.music_list_names[self.now_playing]
         );
     }
-    pub fn highlight_playing(&mut self, id: u16) {
+    pub fn highlight_playing(&mut self, index: usize) {
         self.dehighlight_playing();
-        self.now_playing = id;
+        self.now_playing = index;
+        if self.panel != Panel::List {
+            return;
+        }
         if self.now_playing < self.offset || self.now_playing > (self.lines + self.offset - 1) {
             return;
         }
-        print!("{}", Goto(3, self.now_playing - self.offset + 2));
+        print!("{}", Goto(3, (self.now_playing - self.offset) as u16 + 2));
         if self.cursor_pos == self.now_playing - self.offset + 1 {
             print!(
                 "{}{}. {}",
                 Fg(self.colors.both),
                 self.now_playing + 1,
-                self.music_list_names[self.now_playing as usize]
+                self.music_list_names[self.now_playing]
             );
         } else {
             print!(
                 "{}{}.{}  ",
                 Fg(self.colors.now_playing),
                 self.now_playing + 1,
-                self.music_list_names[self.now_playing as usize]
+                self.music_list_names[self.now_playing]
             );
         };
     }
     pub fn select_track(&mut self) {
-        if self.cursor_pos == 0 {
+        if self.cursor_pos == 0 || self.panel != Panel::List {
             return;
         }
-        print!("{}", Goto(3, self.cursor_pos + 1));
+        print!("{}", Goto(3, self.cursor_pos as u16 + 1));
         if self.now_playing >= self.offset && self.cursor_pos == self.now_playing - self.offset + 1
         {
             print!(
                 "{}{}. {}",
                 Fg(self.colors.both),
                 self.cursor_pos + self.offset,
-                self.music_list_names[(self.cursor_pos - 1 + self.offset) as usize]
+                self.music_list_names[self.cursor_pos - 1 + self.offset]
             );
         } else {
             print!(
                 "{}{}. {}",
                 Fg(self.colors.selected),
                 self.cursor_pos + self.offset,
-                self.music_list_names[(self.cursor_pos - 1 + self.offset) as usize]
+                self.music_list_names[self.cursor_pos - 1 + self.offset]
             );
         }
         self.stdout.flush().unwrap();
     }
     fn deselect_track(&mut self) {
-        if self.cursor_pos == 0 {
+        if self.cursor_pos == 0 || self.panel != Panel::List {
             return;
         }
-        print!("{}", Goto(3, self.cursor_pos + 1));
+        print!("{}", Goto(3, self.cursor_pos as u16 + 1));
         print!(
             "{}{}.{} ",
             RESET_FG,
             self.cursor_pos + self.offset,
-            self.music_list_names[(self.cursor_pos - 1 + self.offset) as usize]
+            self.music_list_names[self.cursor_pos - 1 + self.offset]
         );
         self.highlight_playing(self.now_playing);
     }
     pub fn select_next_track(&mut self) {
-        if (self.cursor_pos + self.offset) as usize >= self.music_list_names.len() {
+        if self.cursor_pos + self.offset >= self.music_list_names.len() {
             return;
         }
         self.deselect_track();
@@ -334,14 +532,14 @@ impl Ui {
         self.deselect_track();
         self.select_track();
     }
-    pub fn highlight_searched(&mut self, search_str: &str) {
+    pub fn highlight_searched(&mut self, found: Option<usize>) {
+        //what was found is in the music list
+        if self.panel != Panel::List {
+            self.show_panel(Panel::List);
+        }
         self.deselect_track();
-        let search_id = match self
-            .music_list_names
-            .iter()
-            .position(|s| s.to_lowercase().contains(search_str))
-        {
-            Some(i) => (i + 1) as u16,
+        let search_id = match found {
+            Some(i) => i + 1,
             None => self.cursor_pos + self.offset,
         };
         if search_id > self.lines {
@@ -357,6 +555,10 @@ impl Ui {
         self.select_track();
     }
     pub fn display_searching(&mut self, s: &str) {
+        self.display_prompt("Search", s);
+    }
+    //shows what the user is typing in place of the queue
+    pub fn display_prompt(&mut self, prompt: &str, s: &str) {
         self.clear_area(
             self.queue_coords[0],
             self.queue_coords[1],
@@ -364,7 +566,7 @@ impl Ui {
             self.queue_coords[1],
         );
         print!("{}", Goto(self.queue_coords[0], self.queue_coords[1]));
-        print!("Search: {}", s);
+        print!("{}: {}", prompt, s);
         self.stdout.flush().unwrap();
     }
     //clear a given area
@@ -391,7 +593,15 @@ fn draw_horizontal_lines(term_width: u16, char: &str) {
     }
 }
 
-fn init_music_list(list: &Vec<String>, lines: u16) {
+fn list_name(track: &Track, term_x: u16) -> String {
+    let name = match track.broken {
+        true => format!("[broken] {}", track.display_name()),
+        false => track.display_name(),
+    };
+    cut_string(name, (term_x - 8).into())
+}
+
+fn init_music_list(list: &[String], lines: u16) {
     print!("{}", Goto(3, 2));
     for i in 0..lines {
         print!(
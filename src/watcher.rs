@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use nix::{
+    errno::Errno,
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+};
+
+use crate::formats;
+
+//something that happened under the music dirs, the paths can be directories too, then it
+//applies to everything under them
+pub enum Change {
+    //a new file, or one that was written to again
+    Added(PathBuf),
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+}
+
+//watches the music dirs from a thread of its own, the player picks the changes up every tick
+pub struct Watcher {
+    changes: Receiver<Change>,
+}
+
+impl Watcher {
+    //None when inotify isnt available, the library then just stays as it was loaded
+    pub fn new(roots: &[PathBuf], depth: usize) -> Option<Self> {
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC).ok()?;
+        let mut watches = Watches { inotify, dirs: HashMap::new() };
+        for root in roots {
+            let Ok(root) = fs::canonicalize(root) else { continue };
+            if root.is_dir() {
+                watches.add_tree(&root, depth, &mut Vec::new());
+            }
+        }
+        let (sender, changes) = mpsc::channel();
+        thread::spawn(move || watches.run(sender));
+        Some(Self { changes })
+    }
+    //everything that happened since the last call
+    pub fn changes(&self) -> Vec<Change> {
+        self.changes.try_iter().collect()
+    }
+}
+
+const MASK: AddWatchFlags = AddWatchFlags::IN_CREATE
+    .union(AddWatchFlags::IN_CLOSE_WRITE)
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_MOVED_FROM)
+    .union(AddWatchFlags::IN_MOVED_TO)
+    .union(AddWatchFlags::IN_ONLYDIR);
+
+struct Watches {
+    inotify: Inotify,
+    //every watched directory and how much deeper scanning would have gone from it
+    dirs: HashMap<WatchDescriptor, (PathBuf, usize)>,
+}
+
+impl Watches {
+    fn run(mut self, sender: Sender<Change>) {
+        loop {
+            let events = match self.inotify.read_events() {
+                Ok(events) => events,
+                Err(Errno::EINTR) => continue,
+                Err(_) => return,
+            };
+            //a move inside the library comes as a from and a to with the same cookie, usually
+            //in the same read, whatever is left unpaired was moved in or out
+            let mut moved_from: Vec<(u32, PathBuf, bool)> = Vec::new();
+            let mut changes = Vec::new();
+            for event in events {
+                if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    self.dirs.remove(&event.wd);
+                    continue;
+                }
+                let (Some((dir, depth)), Some(name)) = (self.dirs.get(&event.wd), event.name) else { continue };
+                let (path, depth) = (dir.join(name), *depth);
+                let is_dir = event.mask.contains(AddWatchFlags::IN_ISDIR);
+                if event.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                    moved_from.push((event.cookie, path, is_dir));
+                } else if event.mask.contains(AddWatchFlags::IN_MOVED_TO) {
+                    match moved_from.iter().position(|m| m.0 == event.cookie) {
+                        Some(i) => {
+                            let (_, from, _) = moved_from.remove(i);
+                            self.renamed(from, path, is_dir, &mut changes);
+                        }
+                        None => self.created(path, depth, is_dir, &mut changes),
+                    }
+                } else if event.mask.contains(AddWatchFlags::IN_CREATE) {
+                    //files are picked up once they are written, only dirs and links are done here
+                    if is_dir || path.is_symlink() {
+                        self.created(path, depth, is_dir, &mut changes);
+                    }
+                } else if event.mask.contains(AddWatchFlags::IN_CLOSE_WRITE) {
+                    if formats::is_supported(&path) {
+                        changes.push(Change::Added(path));
+                    }
+                } else if event.mask.contains(AddWatchFlags::IN_DELETE) {
+                    changes.push(Change::Removed(path));
+                }
+            }
+            for (_, path, is_dir) in moved_from {
+                if is_dir {
+                    self.remove_tree(&path);
+                }
+                changes.push(Change::Removed(path));
+            }
+            for change in changes {
+                if sender.send(change).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+    fn created(&mut self, path: PathBuf, depth: usize, is_dir: bool, changes: &mut Vec<Change>) {
+        if !is_dir {
+            if formats::is_supported(&path) {
+                changes.push(Change::Added(path));
+            }
+            return;
+        }
+        if depth == 0 {
+            return;
+        }
+        //files can land in it before the watch is there, so it gets walked as well
+        let mut found = Vec::new();
+        self.add_tree(&path, depth - 1, &mut found);
+        changes.extend(found.into_iter().map(Change::Added));
+    }
+    fn renamed(&mut self, from: PathBuf, to: PathBuf, is_dir: bool, changes: &mut Vec<Change>) {
+        if is_dir {
+            //the watches follow the directory, only the paths we know them by change
+            for (dir, _) in self.dirs.values_mut() {
+                if let Ok(rest) = dir.strip_prefix(&from) {
+                    *dir = to.join(rest);
+                }
+            }
+            changes.push(Change::Renamed(from, to));
+            return;
+        }
+        match (formats::is_supported(&from), formats::is_supported(&to)) {
+            (true, true) => changes.push(Change::Renamed(from, to)),
+            (true, false) => changes.push(Change::Removed(from)),
+            (false, true) => changes.push(Change::Added(to)),
+            (false, false) => (),
+        }
+    }
+    //watches dir and the dirs under it, the playable files in them go into found
+    fn add_tree(&mut self, dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+        let Ok(wd) = self.inotify.add_watch(dir, MASK) else { return };
+        //a symlink loop or a dir thats watched already gives back the same descriptor
+        if self.dirs.insert(wd, (dir.to_path_buf(), depth)).is_some() {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                if depth > 0 {
+                    self.add_tree(&path, depth - 1, found);
+                }
+            } else if formats::is_supported(&path) {
+                found.push(path);
+            }
+        }
+    }
+    //stops watching a dir that was moved out of the library
+    fn remove_tree(&mut self, dir: &Path) {
+        let gone: Vec<WatchDescriptor> = self
+            .dirs
+            .iter()
+            .filter(|(_, (path, _))| path.starts_with(dir))
+            .map(|(wd, _)| *wd)
+            .collect();
+        for wd in gone {
+            let _ = self.inotify.rm_watch(wd);
+            self.dirs.remove(&wd);
+        }
+    }
+}